@@ -3,12 +3,12 @@ use crate::{
         promote_purity,
         Purity::{self, *},
     },
-    metadata::{MetadataManager, StorageOperation},
+    metadata::MetadataManager,
 };
 
 use sway_error::warning::{CompileWarning, Warning};
 use sway_error::{error::CompileError, handler::Handler};
-use sway_ir::{Context, FuelVmInstruction, Function, Instruction};
+use sway_ir::{Context, FuelVmInstruction, Function, Instruction, StorageOperation};
 use sway_types::span::Span;
 
 use std::collections::HashMap;
@@ -3,7 +3,7 @@ use crate::{
     language::{ty::TyFunctionDecl, Inline, Purity},
 };
 
-use sway_ir::{Context, MetadataIndex, Metadatum, Value};
+use sway_ir::{Context, InlineHint, MetadataIndex, Metadatum, StorageOperation, Value};
 use sway_types::{SourceId, Span};
 
 use std::{collections::HashMap, path::PathBuf, rc::Rc, sync::Arc};
@@ -21,7 +21,6 @@ pub(crate) struct MetadataManager {
     md_span_cache: HashMap<MetadataIndex, Span>,
     md_file_loc_cache: HashMap<MetadataIndex, (Arc<PathBuf>, Arc<str>)>,
     md_storage_op_cache: HashMap<MetadataIndex, StorageOperation>,
-    md_inline_cache: HashMap<MetadataIndex, Inline>,
     md_test_decl_index_cache: HashMap<MetadataIndex, DeclId<TyFunctionDecl>>,
     md_config_const_name_cache: HashMap<MetadataIndex, Rc<str>>,
 
@@ -33,13 +32,6 @@ pub(crate) struct MetadataManager {
     config_const_name_md_cache: HashMap<Rc<str>, MetadataIndex>,
 }
 
-#[derive(Clone, Copy)]
-pub(crate) enum StorageOperation {
-    Reads,
-    Writes,
-    ReadsWrites,
-}
-
 impl MetadataManager {
     pub(crate) fn md_to_span(
         &mut self,
@@ -49,21 +41,22 @@ impl MetadataManager {
         Self::for_each_md_idx(context, md_idx, |md_idx| {
             self.md_span_cache.get(&md_idx).cloned().or_else(|| {
                 // Create a new span and save it in the cache.
-                md_idx
-                    .get_content(context)
-                    .unwrap_struct("span", 3)
-                    .and_then(|fields| {
-                        let (path, src) = self.md_to_file_location(context, &fields[0])?;
+                let fields = md_idx.get_content(context).unwrap_struct("span", 3)?;
+                let span = match self.md_to_file_location(context, &fields[0]) {
+                    Some((path, src)) => {
                         let start = fields[1].unwrap_integer()?;
                         let end = fields[2].unwrap_integer()?;
                         let source_engine = context.source_engine();
                         let source_id = source_engine.get_source_id(&path);
-                        let span = Span::new(src, start as usize, end as usize, Some(source_id))?;
+                        Span::new(src, start as usize, end as usize, Some(source_id))?
+                    }
+                    // Spans from in-memory sources have no file to read, so the IR resolves them.
+                    None => md_idx.to_span(context).ok()?,
+                };
 
-                        self.md_span_cache.insert(md_idx, span.clone());
+                self.md_span_cache.insert(md_idx, span.clone());
 
-                        Some(span)
-                    })
+                Some(span)
             })
         })
     }
@@ -106,12 +99,7 @@ impl MetadataManager {
                     .unwrap_struct("storage", 1)
                     .and_then(|fields| {
                         fields[0].unwrap_string().and_then(|stor_str| {
-                            let op = match stor_str {
-                                "reads" => Some(StorageOperation::Reads),
-                                "writes" => Some(StorageOperation::Writes),
-                                "readswrites" => Some(StorageOperation::ReadsWrites),
-                                _otherwise => None,
-                            }?;
+                            let op = StorageOperation::from_simple_string(stor_str)?;
 
                             self.md_storage_op_cache.insert(md_idx, op);
 
@@ -122,40 +110,6 @@ impl MetadataManager {
         })
     }
 
-    /// Gets Inline information from metadata index.
-    /// TODO: We temporarily allow this because we need this
-    /// in the sway-ir inliner, but cannot access it. So the code
-    /// itself has been (modified and) copied there. When we decide
-    /// on the right place for Metadata to be
-    /// (and how it can be accessed form sway-ir), this will be fixed.
-    #[allow(dead_code)]
-    pub(crate) fn md_to_inline(
-        &mut self,
-        context: &Context,
-        md_idx: Option<MetadataIndex>,
-    ) -> Option<Inline> {
-        Self::for_each_md_idx(context, md_idx, |md_idx| {
-            self.md_inline_cache.get(&md_idx).copied().or_else(|| {
-                // Create a new inline and save it in the cache.
-                md_idx
-                    .get_content(context)
-                    .unwrap_struct("inline", 1)
-                    .and_then(|fields| fields[0].unwrap_string())
-                    .and_then(|inline_str| {
-                        let inline = match inline_str {
-                            "always" => Some(Inline::Always),
-                            "never" => Some(Inline::Never),
-                            _otherwise => None,
-                        }?;
-
-                        self.md_inline_cache.insert(md_idx, inline);
-
-                        Some(inline)
-                    })
-            })
-        })
-    }
-
     fn md_to_file_location(
         &mut self,
         context: &Context,
@@ -214,23 +168,27 @@ impl MetadataManager {
         span: &Span,
     ) -> Option<MetadataIndex> {
         self.span_md_cache.get(span).copied().or_else(|| {
-            span.source_id().and_then(|source_id| {
-                // Create new metadata.
-                let file_location_md_idx = self.file_location_to_md(context, *source_id)?;
-                let md_idx = MetadataIndex::new_struct(
-                    context,
-                    "span",
-                    vec![
-                        Metadatum::Index(file_location_md_idx),
-                        Metadatum::Integer(span.start() as u64),
-                        Metadatum::Integer(span.end() as u64),
-                    ],
-                );
-
-                self.span_md_cache.insert(span.clone(), md_idx);
+            // Create new metadata.
+            let md_idx = match span.source_id() {
+                Some(source_id) => {
+                    let file_location_md_idx = self.file_location_to_md(context, *source_id)?;
+                    MetadataIndex::new_struct(
+                        context,
+                        "span",
+                        vec![
+                            Metadatum::Index(file_location_md_idx),
+                            Metadatum::Integer(span.start() as u64),
+                            Metadatum::Integer(span.end() as u64),
+                        ],
+                    )
+                }
+                // Spans from in-memory sources have no path, so the IR keeps their text instead.
+                None => MetadataIndex::new_span(context, span),
+            };
+
+            self.span_md_cache.insert(span.clone(), md_idx);
 
-                Some(md_idx)
-            })
+            Some(md_idx)
         })
     }
 
@@ -265,16 +223,16 @@ impl MetadataManager {
                 .copied()
                 .unwrap_or_else(|| {
                     // Create new metadatum.
-                    let field = match purity {
+                    let op = match purity {
                         Purity::Pure => unreachable!("Already checked for Pure above."),
-                        Purity::Reads => "reads",
-                        Purity::Writes => "writes",
-                        Purity::ReadsWrites => "readswrites",
+                        Purity::Reads => StorageOperation::Reads,
+                        Purity::Writes => StorageOperation::Writes,
+                        Purity::ReadsWrites => StorageOperation::ReadsWrites,
                     };
                     let md_idx = MetadataIndex::new_struct(
                         context,
                         "storage",
-                        vec![Metadatum::String(field.to_owned())],
+                        vec![Metadatum::String(op.simple_string().to_owned())],
                     );
 
                     self.storage_op_md_cache.insert(purity, md_idx);
@@ -296,15 +254,11 @@ impl MetadataManager {
                 .copied()
                 .unwrap_or_else(|| {
                     // Create new metadatum.
-                    let field = match inline {
-                        Inline::Always => "always",
-                        Inline::Never => "never",
+                    let hint = match inline {
+                        Inline::Always => InlineHint::Always,
+                        Inline::Never => InlineHint::Never,
                     };
-                    let md_idx = MetadataIndex::new_struct(
-                        context,
-                        "inline",
-                        vec![Metadatum::String(field.to_owned())],
-                    );
+                    let md_idx = MetadataIndex::new_inline_directive(context, hint);
 
                     self.inline_md_cache.insert(inline, md_idx);
 
@@ -362,3 +316,44 @@ impl MetadataManager {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sway_error::handler::Handler;
+
+    use crate::{compile_to_ast, ir_generation::compile_program, namespace, Engines};
+
+    #[test]
+    fn spans_from_in_memory_source() {
+        let engines = Engines::default();
+        let handler = Handler::default();
+        let src = "script; fn main() -> u64 { let x = 42; x }";
+        let programs = compile_to_ast(
+            &handler,
+            &engines,
+            Arc::from(src),
+            namespace::Module::default(),
+            None,
+            "test",
+            &mut sway_utils::PerformanceData::default(),
+        )
+        .unwrap();
+        let context = compile_program(programs.typed.as_ref().unwrap(), false, &engines).unwrap();
+
+        let spans = context
+            .module_iter()
+            .flat_map(|module| module.function_iter(&context))
+            .flat_map(|function| function.instruction_iter(&context))
+            .filter_map(|(_, ins)| ins.get_metadata(&context))
+            .map(|md_idx| md_idx.to_span(&context).unwrap())
+            .collect::<Vec<_>>();
+        assert!(!spans.is_empty());
+        for span in &spans {
+            assert_eq!(span.source_id(), None);
+            assert_eq!(span.as_str(), &src[span.start()..span.end()]);
+        }
+        assert!(spans.iter().any(|span| span.as_str() == "let x = 42;"));
+    }
+}
@@ -8,12 +8,20 @@
 
 use generational_arena::Arena;
 use rustc_hash::FxHashMap;
-use sway_types::SourceEngine;
+use std::sync::Arc;
+use sway_types::{SourceEngine, SourceId};
 
 use crate::{
-    asm::AsmBlockContent, block::BlockContent, function::FunctionContent,
-    local_var::LocalVarContent, metadata::Metadatum, module::Kind, module::ModuleContent,
-    module::ModuleIterator, value::ValueContent, Type, TypeContent,
+    asm::AsmBlockContent,
+    block::BlockContent,
+    function::FunctionContent,
+    local_var::LocalVarContent,
+    metadata::{MetadataIndex, Metadatum},
+    module::Kind,
+    module::ModuleContent,
+    module::ModuleIterator,
+    value::ValueContent,
+    Type, TypeContent,
 };
 
 /// The main IR context handle.
@@ -32,6 +40,10 @@ pub struct Context<'eng> {
     pub(crate) type_map: FxHashMap<TypeContent, Type>,
     pub(crate) asm_blocks: Arena<AsmBlockContent>,
     pub(crate) metadata: Arena<Metadatum>,
+    pub(crate) canonical_metadata: FxHashMap<Metadatum, MetadataIndex>,
+    pub(crate) metadata_sources: FxHashMap<SourceId, Arc<str>>,
+    pub(crate) anonymous_sources: Vec<Arc<str>>,
+    pub(crate) removed_values: FxHashMap<u64, Metadatum>,
 
     pub program_kind: Kind,
 
@@ -51,6 +63,10 @@ impl<'eng> Context<'eng> {
             type_map: Default::default(),
             asm_blocks: Default::default(),
             metadata: Default::default(),
+            canonical_metadata: Default::default(),
+            metadata_sources: Default::default(),
+            anonymous_sources: Default::default(),
+            removed_values: Default::default(),
             next_unique_sym_tag: Default::default(),
             program_kind: Kind::Contract,
         };
@@ -1,3 +1,5 @@
+use sway_types::Span;
+
 /// These errors are for internal IR failures, not designed to be useful to a Sway developer, but
 /// more for users of the `sway-ir` crate, i.e., compiler developers.
 ///
@@ -62,6 +64,19 @@ pub enum IrError {
     VerifyStateDestBadType(String),
     VerifyStateKeyBadType,
     VerifyStateKeyNonPointer(String),
+    VerifyStorageMismatch {
+        function: String,
+        expected: String,
+        found: String,
+        span: Option<Span>,
+    },
+    VerifyStorageMismatchInCall {
+        function: String,
+        callee: String,
+        expected: String,
+        found: String,
+        span: Option<Span>,
+    },
     VerifyStoreMismatchedTypes,
     VerifyStoreToNonPointer(String),
     VerifyUntypedValuePassedToFunction,
@@ -311,6 +326,29 @@ impl fmt::Display for IrError {
                     "Verification failed: Store value and pointer type mismatch."
                 )
             }
+            IrError::VerifyStorageMismatch {
+                function,
+                expected,
+                found,
+                span,
+            } => write!(
+                f,
+                "Verification failed: Function '{function}' with storage attribute '{expected}' \
+                {found} storage{}.",
+                located_at(span)
+            ),
+            IrError::VerifyStorageMismatchInCall {
+                function,
+                callee,
+                expected,
+                found,
+                span,
+            } => write!(
+                f,
+                "Verification failed: Function '{function}' with storage attribute '{expected}' \
+                calls '{callee}' with storage attribute '{found}'{}.",
+                located_at(span)
+            ),
             IrError::VerifyStoreToNonPointer(ty) => {
                 write!(f, "Store must be to a pointer, not a {ty}.")
             }
@@ -376,3 +414,12 @@ impl fmt::Display for IrError {
         }
     }
 }
+
+fn located_at(span: &Option<Span>) -> String {
+    span.as_ref()
+        .map(|span| {
+            let (line, col) = span.start_pos().line_col();
+            format!(" at {line}:{col} `{}`", span.as_str())
+        })
+        .unwrap_or_default()
+}
@@ -71,6 +71,8 @@ pub mod pretty;
 pub use pretty::*;
 pub mod printer;
 pub use printer::*;
+pub mod source_map;
+pub use source_map::*;
 pub mod value;
 pub use value::*;
 pub mod verify;
@@ -1,3 +1,6 @@
+use std::collections::VecDeque;
+
+use rustc_hash::{FxHashMap, FxHashSet};
 use sway_types::SourceId;
 
 /// Associated metadata attached mostly to values.
@@ -8,7 +11,30 @@ use sway_types::SourceId;
 ///
 /// The metadata themselves are opaque to `sway-ir` and are represented with simple value types;
 /// integers, strings, symbols (tags) and lists.
-use crate::context::Context;
+use crate::{
+    context::Context,
+    error::IrError,
+    instruction::Instruction,
+    value::{Value, ValueDatum},
+};
+
+mod abi;
+mod costs;
+mod directives;
+mod facts;
+mod items;
+mod markers;
+mod memory;
+mod removals;
+mod source;
+mod span;
+mod storage;
+pub use abi::{BoundaryKind, CallConv};
+pub use directives::{ArithmeticMode, InlineHint};
+pub use facts::Escapes;
+pub use memory::AggregateKind;
+pub use source::CaptureMode;
+pub use storage::StorageOperation;
 
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Hash)]
 pub struct MetadataIndex(pub generational_arena::Index);
@@ -31,34 +57,34 @@ pub enum Metadatum {
 ///
 /// This function conveniently has all the logic to return the simplest combination of two
 /// `Option<MetadataIndex>`s.
+///
+/// See [`MetadataIndex::merge`], which this is a shorthand for.
 pub fn combine(
     context: &mut Context,
     md_idx_a: &Option<MetadataIndex>,
     md_idx_b: &Option<MetadataIndex>,
 ) -> Option<MetadataIndex> {
-    match (md_idx_a, md_idx_b) {
-        (None, None) => None,
-        (Some(_), None) => *md_idx_a,
-        (None, Some(_)) => *md_idx_b,
-        (Some(idx_a), Some(idx_b)) => {
-            // Rather than potentially making lists of lists, if either are already list we can
-            // merge them together.
-            let mut new_list = Vec::new();
-            if let Metadatum::List(lst_a) = &context.metadata[idx_a.0] {
-                new_list.append(&mut lst_a.clone());
-            } else {
-                new_list.push(*idx_a);
-            }
-            if let Metadatum::List(lst_b) = &context.metadata[idx_b.0] {
-                new_list.append(&mut lst_b.clone());
-            } else {
-                new_list.push(*idx_b);
-            }
-            Some(MetadataIndex(
-                context.metadata.insert(Metadatum::List(new_list)),
-            ))
-        }
+    MetadataIndex::merge(context, *md_idx_a, *md_idx_b)
+}
+
+/// Check that a struct tag is a valid identifier, so that it may be printed and parsed back.
+pub(crate) fn verify_struct_tag(tag: &str) -> Result<(), IrError> {
+    // We could import Regex to match it, but it's a simple identifier style pattern: alpha start
+    // char, alphanumeric for the rest, or underscore anywhere.
+    let mut chs = tag.chars();
+    let Some(ch0) = chs.next() else {
+        return Err(IrError::InvalidMetadatum(
+            "Struct has empty tag.".to_owned(),
+        ));
+    };
+    if !(ch0.is_ascii_alphabetic() || ch0 == '_')
+        || chs.any(|ch| !(ch.is_ascii_alphanumeric() || ch == '_'))
+    {
+        return Err(IrError::InvalidMetadatum(format!(
+            "Invalid struct tag: '{tag}'."
+        )));
     }
+    Ok(())
 }
 
 impl MetadataIndex {
@@ -94,9 +120,594 @@ impl MetadataIndex {
         MetadataIndex(context.metadata.insert(Metadatum::List(els)))
     }
 
+    /// Return the one shared index for `md`, creating it the first time it's requested.
+    ///
+    /// This is used for markers and small enumerations which would otherwise be duplicated for
+    /// every value they're attached to.
+    pub(crate) fn new_canonical(context: &mut Context, md: Metadatum) -> Self {
+        if let Some(md_idx) = context.canonical_metadata.get(&md) {
+            return *md_idx;
+        }
+        let md_idx = MetadataIndex(context.metadata.insert(md.clone()));
+        context.canonical_metadata.insert(md, md_idx);
+        md_idx
+    }
+
+    /// Merge two optional indices into one, for attaching both to the same value or function.
+    ///
+    /// Any lists, including nested ones, are flattened and duplicate elements are dropped, so the
+    /// result is either a single non-list index or a flat list of distinct ones.  If one side is
+    /// `None`, or the merge adds nothing new to one side, that side is returned as is rather than
+    /// allocating a new list.
+    pub fn merge(
+        context: &mut Context,
+        md_idx_a: Option<MetadataIndex>,
+        md_idx_b: Option<MetadataIndex>,
+    ) -> Option<MetadataIndex> {
+        let (md_idx_a, md_idx_b) = match (md_idx_a, md_idx_b) {
+            (None, None) => return None,
+            (Some(md_idx), None) | (None, Some(md_idx)) => return Some(md_idx),
+            (Some(md_idx_a), Some(md_idx_b)) if md_idx_a == md_idx_b => return Some(md_idx_a),
+            (Some(md_idx_a), Some(md_idx_b)) => (md_idx_a, md_idx_b),
+        };
+
+        let mut els = Vec::new();
+        let mut seen = FxHashSet::default();
+        md_idx_a.flatten_into(context, &mut els, &mut seen);
+        md_idx_b.flatten_into(context, &mut els, &mut seen);
+
+        if els.len() == 1 {
+            return Some(els[0]);
+        }
+        for md_idx in [md_idx_a, md_idx_b] {
+            if matches!(md_idx.get_content(context), Metadatum::List(md_idcs) if *md_idcs == els) {
+                return Some(md_idx);
+            }
+        }
+        Some(MetadataIndex::new_list(context, els))
+    }
+
+    fn flatten_into(
+        &self,
+        context: &Context,
+        els: &mut Vec<MetadataIndex>,
+        seen: &mut FxHashSet<MetadataIndex>,
+    ) {
+        match self.get_content(context) {
+            Metadatum::List(md_idcs) => {
+                for md_idx in md_idcs {
+                    md_idx.flatten_into(context, els, seen);
+                }
+            }
+            _otherwise => {
+                if seen.insert(*self) {
+                    els.push(*self);
+                }
+            }
+        }
+    }
+
+    /// Create a struct of custom named metadata, e.g., `!9 = inline "never"`, for frontends and
+    /// tools to attach their own annotations without extending [`Metadatum`].
+    ///
+    /// The tag must be an identifier and each argument an integer, a string or an index to
+    /// existing metadata.
+    pub fn from_parts<S: Into<String>>(
+        context: &mut Context,
+        tag: S,
+        args: Vec<Metadatum>,
+    ) -> Result<Self, IrError> {
+        let tag = tag.into();
+        verify_struct_tag(&tag)?;
+        for arg in &args {
+            match arg {
+                Metadatum::Integer(_) | Metadatum::String(_) => (),
+                Metadatum::Index(md_idx) if context.metadata.contains(md_idx.0) => (),
+                Metadatum::Index(_) => {
+                    return Err(IrError::InvalidMetadatum(format!(
+                        "Argument of '{tag}' refers to a missing metadatum."
+                    )))
+                }
+                _otherwise => {
+                    return Err(IrError::InvalidMetadatum(format!(
+                        "Argument of '{tag}' must be an integer, string or index."
+                    )))
+                }
+            }
+        }
+        Ok(MetadataIndex::new_struct(context, tag, args))
+    }
+
+    /// Return the tag of the struct at this index.  Lists aren't searched; see
+    /// [`MetadataIndex::find_tagged`].
+    pub fn get_tag<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        match self.get_content(context) {
+            Metadatum::Struct(tag, _) => Some(tag),
+            _otherwise => None,
+        }
+    }
+
+    /// Return this index if it's a struct with `tag`, or if it's a list then the first of its
+    /// elements which is.
+    pub fn find_tagged(&self, context: &Context, tag: &str) -> Option<MetadataIndex> {
+        match self.get_content(context) {
+            Metadatum::List(md_idcs) => md_idcs
+                .iter()
+                .find(|md_idx| md_idx.get_tag(context) == Some(tag))
+                .copied(),
+            _otherwise => Some(*self).filter(|md_idx| md_idx.get_tag(context) == Some(tag)),
+        }
+    }
+
+    /// Return the `n`th argument of the struct at this index, if it's an integer.
+    pub fn get_arg_int(&self, context: &Context, n: usize) -> Option<u64> {
+        self.get_arg(context, n).and_then(Metadatum::unwrap_integer)
+    }
+
+    /// Return the `n`th argument of the struct at this index, if it's a string.
+    pub fn get_arg_string<'a>(&self, context: &'a Context, n: usize) -> Option<&'a str> {
+        self.get_arg(context, n).and_then(Metadatum::unwrap_string)
+    }
+
+    /// Return the `n`th argument of the struct at this index, if it's an index.
+    pub fn get_arg_index(&self, context: &Context, n: usize) -> Option<MetadataIndex> {
+        self.get_arg(context, n).and_then(Metadatum::unwrap_index)
+    }
+
+    fn get_arg<'a>(&self, context: &'a Context, n: usize) -> Option<&'a Metadatum> {
+        match self.get_content(context) {
+            Metadatum::Struct(_, fields) => fields.get(n),
+            _otherwise => None,
+        }
+    }
+
     pub fn get_content<'a>(&self, context: &'a Context) -> &'a Metadatum {
         &context.metadata[self.0]
     }
+
+    /// Return the fields of the first struct with `tag` and `num_fields` fields, either at this
+    /// index or, if it's a list, at one of its elements.
+    pub(crate) fn find_struct<'a>(
+        &self,
+        context: &'a Context,
+        tag: &str,
+        num_fields: usize,
+    ) -> Option<&'a [Metadatum]> {
+        self.find_variadic_struct(context, tag)
+            .filter(|fields| fields.len() == num_fields)
+    }
+
+    /// Like [`MetadataIndex::find_struct`] but for structs with any number of fields.
+    pub(crate) fn find_variadic_struct<'a>(
+        &self,
+        context: &'a Context,
+        tag: &str,
+    ) -> Option<&'a [Metadatum]> {
+        self.find_first(
+            context,
+            |md| matches!(md, Metadatum::Struct(t, _) if t == tag),
+        )
+        .and_then(|md| match md {
+            Metadatum::Struct(_, fields) => Some(fields.as_slice()),
+            _otherwise => None,
+        })
+    }
+
+    /// Like [`MetadataIndex::find_struct`] but return the fields of every such struct, for kinds
+    /// of which a single item may have several.
+    pub(crate) fn find_structs<'a>(
+        &self,
+        context: &'a Context,
+        tag: &str,
+        num_fields: usize,
+    ) -> Vec<&'a [Metadatum]> {
+        match self.get_content(context) {
+            Metadatum::List(md_idcs) => md_idcs
+                .iter()
+                .filter_map(|md_idx| md_idx.get_content(context).unwrap_struct(tag, num_fields))
+                .collect(),
+            md => md.unwrap_struct(tag, num_fields).into_iter().collect(),
+        }
+    }
+
+    /// Return the metadatum at this index if it satisfies `pred`, or if it's a list then the first
+    /// of its elements which does.
+    ///
+    /// Only a single level of list is searched; a nested list is passed to `pred` as is rather
+    /// than searched itself.
+    pub fn find_first<'a>(
+        &self,
+        context: &'a Context,
+        pred: impl Fn(&Metadatum) -> bool,
+    ) -> Option<&'a Metadatum> {
+        match self.get_content(context) {
+            Metadatum::List(md_idcs) => md_idcs
+                .iter()
+                .map(|md_idx| md_idx.get_content(context))
+                .find(|md| pred(md)),
+            md => Some(md).filter(|md| pred(md)),
+        }
+    }
+
+    /// Whether there is a struct of the `kind` tag at this index or anywhere within it, if it's a
+    /// list.  Nested lists are searched too, stopping at the first match.
+    pub fn has_kind(&self, context: &Context, kind: &str) -> bool {
+        match self.get_content(context) {
+            Metadatum::Struct(tag, _) => tag == kind,
+            Metadatum::List(md_idcs) => md_idcs.iter().any(|md_idx| md_idx.has_kind(context, kind)),
+            _otherwise => false,
+        }
+    }
+
+    /// Walk a chain of links between metadata, starting at this index and repeatedly following
+    /// the index returned by `pick` until it returns `None`.  The returned chain includes this
+    /// index.
+    ///
+    /// Walking stops after [`MAX_LINK_DEPTH`] links, in case the metadata are cyclic.
+    pub fn follow_links(
+        &self,
+        context: &Context,
+        pick: impl Fn(&Metadatum) -> Option<MetadataIndex>,
+    ) -> Vec<MetadataIndex> {
+        let mut chain = vec![*self];
+        let mut cur_md_idx = *self;
+        while chain.len() <= MAX_LINK_DEPTH {
+            match pick(cur_md_idx.get_content(context)) {
+                Some(next_md_idx) => {
+                    chain.push(next_md_idx);
+                    cur_md_idx = next_md_idx;
+                }
+                None => break,
+            }
+        }
+        chain
+    }
+}
+
+/// The maximum number of links [`MetadataIndex::follow_links`] will follow.
+pub const MAX_LINK_DEPTH: usize = 256;
+
+/// A saved copy of the metadata of a [`Context`], see [`Context::snapshot_metadata`].
+#[derive(Clone)]
+pub struct MetadataSnapshot {
+    metadata: generational_arena::Arena<Metadatum>,
+    canonical_metadata: FxHashMap<Metadatum, MetadataIndex>,
+    metadata_sources: FxHashMap<SourceId, std::sync::Arc<str>>,
+}
+
+impl<'eng> Context<'eng> {
+    /// Save the current metadata so that it may be reverted later with
+    /// [`Context::restore_metadata`].  Source texts are shared rather than copied.
+    pub fn snapshot_metadata(&self) -> MetadataSnapshot {
+        MetadataSnapshot {
+            metadata: self.metadata.clone(),
+            canonical_metadata: self.canonical_metadata.clone(),
+            metadata_sources: self.metadata_sources.clone(),
+        }
+    }
+
+    /// Revert the metadata to a snapshot, discarding everything created since.
+    ///
+    /// Any metadata attached to the IR since the snapshot was taken must be discarded by the
+    /// caller too, as its indices are no longer valid.
+    pub fn restore_metadata(&mut self, snap: MetadataSnapshot) {
+        self.metadata = snap.metadata;
+        self.canonical_metadata = snap.canonical_metadata;
+        self.metadata_sources = snap.metadata_sources;
+    }
+
+    /// Remove every metadatum which doesn't satisfy `pred`, e.g., to drop analysis-only metadata
+    /// from a release build.
+    ///
+    /// Any metadata referred to by a retained metadatum are also retained, such as the source file
+    /// of a span.  `pred` isn't applied to lists; instead they're pruned to their retained
+    /// elements, and removed if none remain.  A list pruned to a single element is replaced by
+    /// that element wherever it's referred to, and the returned map gives the replacement for each
+    /// such list.  The arena isn't compacted, so the indices of all other retained metadata are
+    /// unchanged.  Metadata attached to functions, values and ASM instructions are updated to
+    /// match, and source files no longer referred to are dropped.
+    pub fn retain_metadata(
+        &mut self,
+        pred: impl Fn(&Metadatum) -> bool,
+    ) -> FxHashMap<MetadataIndex, MetadataIndex> {
+        let mut keep = FxHashSet::default();
+        let mut worklist = self
+            .metadata
+            .iter()
+            .filter(|(_, md)| !matches!(md, Metadatum::List(_)) && pred(md))
+            .map(|(arena_idx, _)| MetadataIndex(arena_idx))
+            .collect::<Vec<_>>();
+        while let Some(md_idx) = worklist.pop() {
+            if keep.insert(md_idx) {
+                worklist.append(&mut md_idx.get_content(self).referenced_indices());
+            }
+        }
+
+        // Lists may be nested, so keep going until no more are found to have retained elements.
+        loop {
+            let new_lists = self
+                .metadata
+                .iter()
+                .filter_map(|(arena_idx, md)| match md {
+                    Metadatum::List(md_idcs)
+                        if !keep.contains(&MetadataIndex(arena_idx))
+                            && md_idcs.iter().any(|md_idx| keep.contains(md_idx)) =>
+                    {
+                        Some(MetadataIndex(arena_idx))
+                    }
+                    _otherwise => None,
+                })
+                .collect::<Vec<_>>();
+            if new_lists.is_empty() {
+                break;
+            }
+            keep.extend(new_lists);
+        }
+
+        self.metadata
+            .retain(|arena_idx, _| keep.contains(&MetadataIndex(arena_idx)));
+        for (_, md) in self.metadata.iter_mut() {
+            if let Metadatum::List(md_idcs) = md {
+                md_idcs.retain(|md_idx| keep.contains(md_idx));
+            }
+        }
+
+        // Lists which were pruned to a single element are replaced by that element, as lists
+        // are expected to contain at least two.  The element may itself be such a list.
+        let singletons = self
+            .metadata
+            .iter()
+            .filter_map(|(arena_idx, md)| match md {
+                Metadatum::List(md_idcs) if md_idcs.len() == 1 => {
+                    Some((MetadataIndex(arena_idx), md_idcs[0]))
+                }
+                _otherwise => None,
+            })
+            .collect::<FxHashMap<_, _>>();
+        let collapsed = singletons
+            .keys()
+            .map(|list_md_idx| {
+                let mut el_md_idx = singletons[list_md_idx];
+                while let Some(next_md_idx) = singletons.get(&el_md_idx) {
+                    el_md_idx = *next_md_idx;
+                }
+                (*list_md_idx, el_md_idx)
+            })
+            .collect::<FxHashMap<_, _>>();
+        self.metadata
+            .retain(|arena_idx, _| !collapsed.contains_key(&MetadataIndex(arena_idx)));
+        for (_, md) in self.metadata.iter_mut() {
+            md.replace_indices(&collapsed);
+        }
+
+        // Canonical metadata may have had their contents updated above, so rekey them.
+        self.canonical_metadata = std::mem::take(&mut self.canonical_metadata)
+            .into_values()
+            .filter_map(|md_idx| self.metadata.get(md_idx.0).map(|md| (md.clone(), md_idx)))
+            .collect();
+        self.retain_referenced_sources();
+        self.for_each_attached_metadata_mut(|md_idx| {
+            if let Some(old_md_idx) = md_idx {
+                if !keep.contains(old_md_idx) {
+                    *md_idx = None;
+                } else if let Some(el_md_idx) = collapsed.get(old_md_idx) {
+                    *md_idx = Some(*el_md_idx);
+                }
+            }
+        });
+        collapsed
+    }
+
+    /// Remove every metadatum which can't be reached from the IR, e.g., after DCE or inlining has
+    /// removed the instructions which used it.
+    ///
+    /// The roots are the metadata attached to each module, its global constants and
+    /// configurables, its functions and their arguments, and the block arguments, instructions,
+    /// instruction operands and ASM instructions in each function.  Anything they refer to, such
+    /// as the source file of a span or the elements of a list, is kept too.  Indices of the kept
+    /// metadata are unchanged, shared canonical indices which are removed will be recreated
+    /// afresh, and metadata attached to values which are no longer in the IR is cleared.
+    ///
+    /// Caches of indices held outside of the context, like those in `sway-core`'s metadata
+    /// manager, aren't updated and must be discarded.
+    pub fn gc_metadata(&mut self) {
+        let mut worklist = self.attached_metadata();
+        let mut keep = FxHashSet::default();
+        while let Some(md_idx) = worklist.pop() {
+            if keep.insert(md_idx) {
+                worklist.append(&mut md_idx.get_content(self).referenced_indices());
+            }
+        }
+
+        self.metadata
+            .retain(|arena_idx, _| keep.contains(&MetadataIndex(arena_idx)));
+        self.canonical_metadata
+            .retain(|_, md_idx| keep.contains(md_idx));
+        self.retain_referenced_sources();
+        self.for_each_attached_metadata_mut(|md_idx| {
+            if matches!(md_idx, Some(old_md_idx) if !keep.contains(old_md_idx)) {
+                *md_idx = None;
+            }
+        });
+    }
+
+    /// Remove all metadata except for spans and the source files they refer to.  See
+    /// [`Context::retain_metadata`] for the returned map.
+    pub fn keep_only_spans(&mut self) -> FxHashMap<MetadataIndex, MetadataIndex> {
+        self.retain_metadata(|md| md.unwrap_struct("span", 3).is_some())
+    }
+
+    /// Drop the text of source files which no metadatum refers to any more.
+    fn retain_referenced_sources(&mut self) {
+        let source_ids = self
+            .metadata
+            .iter()
+            .filter_map(|(_, md)| md.unwrap_source_id().copied())
+            .collect::<FxHashSet<_>>();
+        self.metadata_sources
+            .retain(|source_id, _| source_ids.contains(source_id));
+    }
+
+    /// Return an iterator for the metadata attached to constant and configurable values.  Where
+    /// a constant has a list of metadata attached each element is returned individually.
+    pub fn constant_metadata(&self) -> impl Iterator<Item = (MetadataIndex, &Metadatum)> {
+        self.values
+            .iter()
+            .filter(|(_, value)| {
+                matches!(
+                    value.value,
+                    ValueDatum::Constant(_) | ValueDatum::Configurable(_)
+                )
+            })
+            .filter_map(|(_, value)| value.metadata)
+            .flat_map(|md_idx| match md_idx.get_content(self) {
+                Metadatum::List(md_idcs) => md_idcs.clone(),
+                _otherwise => vec![md_idx],
+            })
+            .map(|md_idx| (md_idx, md_idx.get_content(self)))
+    }
+
+    /// Return the metadata attached to everything still in the IR, once for each place it's
+    /// attached.  See [`Context::attached_metadata_sites`].
+    pub(crate) fn attached_metadata(&self) -> Vec<MetadataIndex> {
+        self.attached_metadata_sites()
+            .into_iter()
+            .map(|(_, md_idx)| md_idx)
+            .collect()
+    }
+
+    /// Return the metadata attached to everything still in the IR, along with the value it's
+    /// attached to, if it's a value at all.
+    ///
+    /// These are each module, its global constants and configurables, its functions and their
+    /// arguments, and the block arguments, instructions, instruction operands and ASM
+    /// instructions in each function, in that order.  Instructions which have been removed from
+    /// their blocks aren't included.  Where an index is attached in several places, e.g., copies
+    /// of an instruction made by the inliner, it's returned for each of them.
+    pub(crate) fn attached_metadata_sites(&self) -> Vec<(Option<Value>, MetadataIndex)> {
+        let mut sites = Vec::new();
+        let mut seen_values = FxHashSet::default();
+        for (_, module) in self.modules.iter() {
+            sites.extend(module.metadata.map(|md_idx| (None, md_idx)));
+            let mut global_constants = module.global_constants.iter().collect::<Vec<_>>();
+            global_constants.sort_by_key(|(name, _)| *name);
+            let globals = global_constants
+                .into_iter()
+                .chain(module.global_configurable.iter())
+                .map(|(_, value)| *value);
+            self.add_value_sites(&mut sites, &mut seen_values, globals);
+            for function in &module.functions {
+                let function = &self.functions[function.0];
+                sites.extend(function.metadata.map(|md_idx| (None, md_idx)));
+                let args = function.arguments.iter().map(|(_, arg)| *arg);
+                self.add_value_sites(&mut sites, &mut seen_values, args);
+                for block in &function.blocks {
+                    let block = &self.blocks[block.0];
+                    self.add_value_sites(&mut sites, &mut seen_values, block.args.iter().copied());
+                    for ins_val in &block.instructions {
+                        self.add_value_sites(&mut sites, &mut seen_values, [*ins_val]);
+                        let ValueDatum::Instruction(ins) = &self.values[ins_val.0].value else {
+                            continue;
+                        };
+                        self.add_value_sites(&mut sites, &mut seen_values, ins.get_operands());
+                        if let Instruction::AsmBlock(asm_block, _) = ins {
+                            sites.extend(
+                                self.asm_blocks[asm_block.0]
+                                    .body
+                                    .iter()
+                                    .filter_map(|asm_ins| asm_ins.metadata)
+                                    .map(|md_idx| (None, md_idx)),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        sites
+    }
+
+    fn add_value_sites(
+        &self,
+        sites: &mut Vec<(Option<Value>, MetadataIndex)>,
+        seen_values: &mut FxHashSet<Value>,
+        values: impl IntoIterator<Item = Value>,
+    ) {
+        for value in values {
+            if seen_values.insert(value) {
+                sites.extend(
+                    self.values[value.0]
+                        .metadata
+                        .map(|md_idx| (Some(value), md_idx)),
+                );
+            }
+        }
+    }
+
+    /// Call `f` for the metadata attached to every function, value and ASM instruction.
+    pub(crate) fn for_each_attached_metadata_mut(
+        &mut self,
+        mut f: impl FnMut(&mut Option<MetadataIndex>),
+    ) {
+        for (_, module) in self.modules.iter_mut() {
+            f(&mut module.metadata);
+        }
+        for (_, function) in self.functions.iter_mut() {
+            f(&mut function.metadata);
+        }
+        for (_, value) in self.values.iter_mut() {
+            f(&mut value.metadata);
+        }
+        for (_, asm_block) in self.asm_blocks.iter_mut() {
+            for asm_ins in asm_block.body.iter_mut() {
+                f(&mut asm_ins.metadata);
+            }
+        }
+    }
+
+    /// Return every metadata index ordered so that each comes after all of those it references,
+    /// e.g., a file before the spans within it.  Metadata with nothing left to wait on are taken
+    /// breadth first, in arena order, so the order is deterministic.
+    ///
+    /// Returns an error if the metadata contain a cycle, as then no such order exists.
+    pub fn metadata_topo_order(&self) -> Result<Vec<MetadataIndex>, IrError> {
+        let mut num_deps = FxHashMap::default();
+        let mut users = FxHashMap::<MetadataIndex, Vec<MetadataIndex>>::default();
+        let mut ready = VecDeque::new();
+        for (arena_idx, md) in self.metadata.iter() {
+            let md_idx = MetadataIndex(arena_idx);
+            let mut deps = md.referenced_indices();
+            deps.retain(|dep_idx| self.metadata.contains(dep_idx.0));
+            deps.sort();
+            deps.dedup();
+            for dep_idx in &deps {
+                users.entry(*dep_idx).or_default().push(md_idx);
+            }
+            if deps.is_empty() {
+                ready.push_back(md_idx);
+            }
+            num_deps.insert(md_idx, deps.len());
+        }
+
+        let mut order = Vec::with_capacity(self.metadata.len());
+        while let Some(md_idx) = ready.pop_front() {
+            order.push(md_idx);
+            for user_idx in users.get(&md_idx).into_iter().flatten() {
+                let count = num_deps.get_mut(user_idx).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(*user_idx);
+                }
+            }
+        }
+
+        if order.len() == self.metadata.len() {
+            Ok(order)
+        } else {
+            Err(IrError::InvalidMetadatum(
+                "Metadata contain a cycle, they cannot be ordered.".to_owned(),
+            ))
+        }
+    }
 }
 
 impl Metadatum {
@@ -146,4 +757,626 @@ impl Metadatum {
             None
         }
     }
+
+    /// 32 byte values (storage keys, hashes, etc.) are stored as `0x` prefixed hex strings.
+    pub(crate) fn new_b256(bytes: &[u8; 32]) -> Metadatum {
+        Metadatum::String(format!(
+            "0x{}",
+            bytes.iter().map(|b| format!("{b:02x}")).collect::<String>()
+        ))
+    }
+
+    /// Return every index this metadatum refers to directly, i.e., not recursively.
+    pub(crate) fn referenced_indices(&self) -> Vec<MetadataIndex> {
+        match self {
+            Metadatum::Integer(_) | Metadatum::String(_) | Metadatum::SourceId(_) => Vec::new(),
+            Metadatum::Index(idx) => vec![*idx],
+            Metadatum::Struct(_, fields) => fields
+                .iter()
+                .flat_map(Metadatum::referenced_indices)
+                .collect(),
+            Metadatum::List(idcs) => idcs.clone(),
+        }
+    }
+
+    /// Replace each index referred to by this metadatum which is in `replacements`.
+    fn replace_indices(&mut self, replacements: &FxHashMap<MetadataIndex, MetadataIndex>) {
+        match self {
+            Metadatum::Integer(_) | Metadatum::String(_) | Metadatum::SourceId(_) => (),
+            Metadatum::Index(idx) => {
+                if let Some(new_idx) = replacements.get(idx) {
+                    *idx = *new_idx;
+                }
+            }
+            Metadatum::Struct(_, fields) => {
+                for field in fields {
+                    field.replace_indices(replacements);
+                }
+            }
+            Metadatum::List(idcs) => {
+                for idx in idcs {
+                    if let Some(new_idx) = replacements.get(idx) {
+                        *idx = *new_idx;
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn unwrap_b256(&self) -> Option<[u8; 32]> {
+        let hex = self.unwrap_string()?.strip_prefix("0x")?;
+        if hex.len() != 64 {
+            return None;
+        }
+        let mut bytes = [0; 32];
+        for (idx, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(hex.get(idx * 2..idx * 2 + 2)?, 16).ok()?;
+        }
+        Some(bytes)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{combine, span::tests::new_test_span, MetadataIndex, Metadatum};
+
+    use crate::{
+        block::Block,
+        constant::Constant,
+        context::Context,
+        error::IrError,
+        function::Function,
+        irtype::Type,
+        module::{Kind, Module},
+        value::Value,
+    };
+    use sway_types::SourceEngine;
+
+    /// Create a function in a new script module with a `nop` for each of `md_idcs`, with that
+    /// metadata attached, returning its block and the `nop`s.
+    pub(crate) fn new_test_nops(
+        context: &mut Context,
+        md_idcs: &[MetadataIndex],
+    ) -> (Block, Vec<Value>) {
+        let module = Module::new(context, Kind::Script);
+        let unit_ty = Type::get_unit(context);
+        let function = Function::new(
+            context,
+            module,
+            "main".to_owned(),
+            Vec::new(),
+            unit_ty,
+            None,
+            false,
+            true,
+            None,
+        );
+        let block = function.get_entry_block(context);
+        let nops = md_idcs
+            .iter()
+            .map(|md_idx| {
+                block
+                    .ins(context)
+                    .nop()
+                    .add_metadatum(context, Some(*md_idx))
+            })
+            .collect();
+        (block, nops)
+    }
+
+    /// A way of creating a metadatum, paired with a check that an index holds exactly what it
+    /// creates.
+    pub(crate) type RoundTrip = (
+        fn(&mut Context) -> MetadataIndex,
+        fn(&MetadataIndex, &Context) -> bool,
+    );
+
+    /// Check that each of `round_trips` reads back what it creates, both by itself and combined
+    /// with a span, and that its check fails for the span alone and for what the others create.
+    pub(crate) fn check_round_trips(round_trips: &[RoundTrip]) {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 3, 7);
+        let md_idcs = round_trips
+            .iter()
+            .map(|(new_md, _)| new_md(&mut context))
+            .collect::<Vec<_>>();
+        for (md_pos, md_idx) in md_idcs.iter().enumerate() {
+            let both_md_idx = combine(&mut context, &Some(span_md_idx), &Some(*md_idx)).unwrap();
+            assert_eq!(both_md_idx.to_span(&context).unwrap().as_str(), "main");
+            for (check_pos, (_, check)) in round_trips.iter().enumerate() {
+                let expected = check_pos == md_pos;
+                assert_eq!(check(md_idx, &context), expected, "{check_pos} on {md_pos}");
+                assert_eq!(
+                    check(&both_md_idx, &context),
+                    expected,
+                    "{check_pos} on {md_pos}"
+                );
+            }
+        }
+        for (check_pos, (_, check)) in round_trips.iter().enumerate() {
+            assert!(!check(&span_md_idx, &context), "{check_pos} on a span");
+        }
+    }
+
+    #[test]
+    fn attached_metadata_sites() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let shared = MetadataIndex::new_integer(&mut context, 1);
+        let removed = MetadataIndex::new_integer(&mut context, 2);
+        let (block, nops) = new_test_nops(&mut context, &[shared, removed, shared]);
+        block.remove_instruction(&mut context, nops[1]);
+
+        // The operand of the `ret` is in the IR too, but only counted once.
+        let operand = MetadataIndex::new_integer(&mut context, 3);
+        let unit = Constant::new_unit(&context);
+        let unit_val = Value::new_constant(&mut context, unit.clone())
+            .add_metadatum(&mut context, Some(operand));
+        let unit_ty = Type::get_unit(&context);
+        let ret_val = block.ins(&mut context).ret(unit_val, unit_ty);
+
+        // Values which aren't in the IR aren't counted.
+        Value::new_constant(&mut context, unit).add_metadatum(&mut context, Some(removed));
+
+        assert_eq!(
+            context.attached_metadata_sites(),
+            vec![
+                (Some(nops[0]), shared),
+                (Some(nops[2]), shared),
+                (Some(unit_val), operand),
+            ]
+        );
+        assert_eq!(ret_val.get_metadata(&context), None);
+    }
+
+    #[test]
+    fn has_kind_in_nested_list() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let storage = MetadataIndex::new_struct(
+            &mut context,
+            "storage",
+            vec![Metadatum::String("reads".to_owned())],
+        );
+        let int = MetadataIndex::new_integer(&mut context, 42);
+        let inner = MetadataIndex::new_list(&mut context, vec![int, storage]);
+        let outer = MetadataIndex::new_list(&mut context, vec![int, inner]);
+
+        assert!(outer.has_kind(&context, "storage"));
+        assert!(storage.has_kind(&context, "storage"));
+        assert!(!outer.has_kind(&context, "inline"));
+        assert!(!int.has_kind(&context, "storage"));
+    }
+
+    #[test]
+    fn merge() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span = new_test_span(&mut context, "a.sw", 0, 10);
+        let storage = MetadataIndex::new_struct(
+            &mut context,
+            "storage",
+            vec![Metadatum::String("reads".to_owned())],
+        );
+        let int = MetadataIndex::new_integer(&mut context, 42);
+
+        // Merging with `None` or with itself doesn't allocate.
+        let md_count = context.metadata.len();
+        assert_eq!(MetadataIndex::merge(&mut context, None, None), None);
+        assert_eq!(
+            MetadataIndex::merge(&mut context, Some(span), None),
+            Some(span)
+        );
+        assert_eq!(
+            MetadataIndex::merge(&mut context, None, Some(int)),
+            Some(int)
+        );
+        assert_eq!(
+            MetadataIndex::merge(&mut context, Some(span), Some(span)),
+            Some(span)
+        );
+        assert_eq!(context.metadata.len(), md_count);
+
+        let span_storage = MetadataIndex::merge(&mut context, Some(span), Some(storage)).unwrap();
+        assert_eq!(span_storage.to_span(&context).unwrap().start(), 0);
+        assert!(span_storage.has_kind(&context, "storage"));
+
+        // Lists which share entries are merged without duplicates, and nested lists flattened.
+        let storage_int = MetadataIndex::new_list(&mut context, vec![storage, int]);
+        let nested = MetadataIndex::new_list(&mut context, vec![span, storage_int]);
+        let merged = MetadataIndex::merge(&mut context, Some(span_storage), Some(nested)).unwrap();
+        assert_eq!(
+            merged.get_content(&context).unwrap_list(),
+            Some([span, storage, int].as_slice())
+        );
+
+        // Nothing new is added, so the existing list is reused.
+        assert_eq!(
+            MetadataIndex::merge(&mut context, Some(merged), Some(storage)),
+            Some(merged)
+        );
+    }
+
+    #[test]
+    fn gc_metadata() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        // Spans are shared, so making the same one twice doesn't grow the metadata.
+        let fn_span = new_test_span(&mut context, "a.sw", 0, 28);
+        assert_eq!(fn_span, new_test_span(&mut context, "a.sw", 0, 28));
+
+        let module = Module::new(&mut context, Kind::Script);
+        let unit_ty = Type::get_unit(&context);
+        let function = Function::new(
+            &mut context,
+            module,
+            "main".to_owned(),
+            Vec::new(),
+            unit_ty,
+            None,
+            false,
+            true,
+            Some(fn_span),
+        );
+        let block = function.get_entry_block(&context);
+        let mut nops = Vec::new();
+        for start in 0..8 {
+            let span = new_test_span(&mut context, "a.sw", start, start + 2);
+            let count = MetadataIndex::new_integer(&mut context, start as u64);
+            let md_idx = MetadataIndex::new_list(&mut context, vec![span, count]);
+            nops.push(
+                block
+                    .ins(&mut context)
+                    .nop()
+                    .add_metadatum(&mut context, Some(md_idx)),
+            );
+        }
+        let unit = Constant::new_unit(&context);
+        let unit_val = Value::new_constant(&mut context, unit);
+        block.ins(&mut context).ret(unit_val, unit_ty);
+        let garbage = MetadataIndex::new_string(&mut context, "unused");
+
+        let removed = nops.iter().step_by(2).copied().collect::<Vec<_>>();
+        block.remove_instructions(&mut context, |value| removed.contains(&value));
+        let md_count = context.metadata.len();
+        context.gc_metadata();
+        // Each removed nop had a span, an integer and a list.
+        assert_eq!(context.metadata.len(), md_count - 4 * 3 - 1);
+        assert!(!context.metadata.contains(garbage.0));
+
+        assert_eq!(fn_span.to_span(&context).unwrap().end(), 28);
+        for (start, nop) in nops.iter().enumerate() {
+            let md_idx = nop.get_metadata(&context);
+            if removed.contains(nop) {
+                assert_eq!(md_idx, None);
+            } else {
+                assert_eq!(md_idx.unwrap().to_span(&context).unwrap().start(), start);
+            }
+        }
+
+        // A removed span is made afresh rather than reusing the stale index.
+        let span = new_test_span(&mut context, "a.sw", 0, 2);
+        assert!(context.metadata.contains(span.0));
+    }
+
+    #[test]
+    fn named_metadata_round_trip() {
+        let source_engine = SourceEngine::default();
+        let context = crate::parse(
+            r#"contract {
+    fn transfer<2151bd4b>() -> (), !3 {
+        entry():
+        v0 = const unit ()
+        ret () v0
+    }
+}
+
+!0 = profile_tag "hot" 3
+!1 = selector 0x2151bd4b !0
+!2 = inline "never"
+!3 = (!1 !2)
+"#,
+            &source_engine,
+        )
+        .unwrap();
+
+        let check = |context: &Context| {
+            let function = context.functions.iter().next().unwrap().1;
+            let md_idx = function.metadata.unwrap();
+            let inline = md_idx.find_tagged(context, "inline").unwrap();
+            assert_eq!(inline.get_arg_string(context, 0), Some("never"));
+            assert_eq!(
+                md_idx.to_inline_directive(context),
+                Some(super::InlineHint::Never)
+            );
+
+            let selector = md_idx.find_tagged(context, "selector").unwrap();
+            assert_eq!(selector.get_arg_int(context, 0), Some(0x2151bd4b));
+            assert_eq!(selector.get_arg_string(context, 0), None);
+            let profile_tag = selector.get_arg_index(context, 1).unwrap();
+            assert_eq!(profile_tag.get_tag(context), Some("profile_tag"));
+            assert_eq!(profile_tag.get_arg_string(context, 0), Some("hot"));
+            assert_eq!(profile_tag.get_arg_int(context, 1), Some(3));
+            assert_eq!(profile_tag.get_arg_int(context, 2), None);
+            assert_eq!(md_idx.find_tagged(context, "profile_tag"), None);
+        };
+        check(&context);
+
+        let printed = crate::printer::to_string(&context);
+        let reparsed = crate::parse(&printed, &source_engine).unwrap();
+        check(&reparsed);
+        assert_eq!(crate::printer::to_string(&reparsed), printed);
+    }
+
+    #[test]
+    fn from_parts() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let int = MetadataIndex::new_integer(&mut context, 1);
+        let md_idx = MetadataIndex::from_parts(
+            &mut context,
+            "test_attr",
+            vec![Metadatum::String("a".to_owned()), Metadatum::Index(int)],
+        )
+        .unwrap();
+        assert_eq!(md_idx.get_tag(&context), Some("test_attr"));
+        assert_eq!(md_idx.get_arg_index(&context, 1), Some(int));
+        assert_eq!(int.get_tag(&context), None);
+
+        assert!(MetadataIndex::from_parts(&mut context, "1st", Vec::new()).is_err());
+        assert!(MetadataIndex::from_parts(&mut context, "", Vec::new()).is_err());
+        assert!(MetadataIndex::from_parts(
+            &mut context,
+            "nested",
+            vec![Metadatum::Struct("inner".to_owned(), Vec::new())]
+        )
+        .is_err());
+        context.metadata.remove(int.0);
+        assert!(
+            MetadataIndex::from_parts(&mut context, "dangling", vec![Metadatum::Index(int)])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn verify_metadata_cycle() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let module = Module::new(&mut context, Kind::Script);
+        let unit_ty = Type::get_unit(&context);
+        let int = MetadataIndex::new_integer(&mut context, 1);
+        let link =
+            MetadataIndex::from_parts(&mut context, "link", vec![Metadatum::Index(int)]).unwrap();
+        let list = MetadataIndex::new_list(&mut context, vec![int, link]);
+        let function = Function::new(
+            &mut context,
+            module,
+            "main".to_owned(),
+            Vec::new(),
+            unit_ty,
+            None,
+            false,
+            true,
+            Some(list),
+        );
+        let unit = Constant::new_unit(&context);
+        let unit_val = Value::new_constant(&mut context, unit);
+        function
+            .get_entry_block(&context)
+            .ins(&mut context)
+            .ret(unit_val, unit_ty);
+        let mut context = context.verify().unwrap();
+
+        // Make the link point back at the list containing it.
+        context.metadata[link.0] =
+            Metadatum::Struct("link".to_owned(), vec![Metadatum::Index(list)]);
+        assert!(matches!(
+            context.verify().err(),
+            Some(IrError::InvalidMetadatum(msg)) if msg.contains("cycle")
+        ));
+    }
+
+    #[test]
+    fn follow_links() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let end = MetadataIndex::new_integer(&mut context, 0);
+        let mid = MetadataIndex::new_index(&mut context, end);
+        let start = MetadataIndex::new_index(&mut context, mid);
+        assert_eq!(
+            start.follow_links(&context, Metadatum::unwrap_index),
+            vec![start, mid, end]
+        );
+        assert_eq!(
+            end.follow_links(&context, Metadatum::unwrap_index),
+            vec![end]
+        );
+
+        // Close the loop; the walk must still terminate.
+        context.metadata[end.0] = Metadatum::Index(start);
+        assert_eq!(
+            start.follow_links(&context, Metadatum::unwrap_index).len(),
+            super::MAX_LINK_DEPTH + 1
+        );
+    }
+
+    #[test]
+    fn keep_only_spans() {
+        let source_engine = SourceEngine::default();
+        let mut context = crate::parse(
+            r#"script {
+    entry fn main() -> (), !2 {
+        entry():
+        v0 = const unit ()
+        ret () v0, !3
+    }
+}
+
+!0 = "a.sw"
+!1 = span !0 0 4
+!2 = storage "reads"
+!3 = (!1 !2)
+"#,
+            &source_engine,
+        )
+        .unwrap();
+
+        let collapsed = context.keep_only_spans();
+
+        // Just the file and the span; the list is collapsed to the span.
+        assert_eq!(context.metadata.len(), 2);
+        assert_eq!(collapsed.len(), 1);
+        assert!(!context
+            .metadata
+            .iter()
+            .any(|(_, md)| md.unwrap_struct("storage", 1).is_some()));
+
+        let function = context
+            .module_iter()
+            .next()
+            .unwrap()
+            .function_iter(&context)
+            .next()
+            .unwrap();
+        assert!(function.get_metadata(&context).is_none());
+
+        let (_, ret_val) = function.instruction_iter(&context).next().unwrap();
+        let ret_md_idx = ret_val.get_metadata(&context).unwrap();
+        assert!(!ret_md_idx.has_kind(&context, "storage"));
+        assert!(ret_md_idx.has_kind(&context, "span"));
+        assert!(ret_md_idx.get_content(&context).unwrap_list().is_none());
+        assert_eq!(collapsed.values().next(), Some(&ret_md_idx));
+    }
+
+    #[test]
+    fn retain_metadata_collapses_nested_lists() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span = new_test_span(&mut context, "a.sw", 3, 7);
+        let reads = MetadataIndex::new_struct(
+            &mut context,
+            "storage",
+            vec![Metadatum::String("reads".to_owned())],
+        );
+        let inner = MetadataIndex::new_list(&mut context, vec![span, reads]);
+        let outer = MetadataIndex::new_list(&mut context, vec![inner, reads]);
+
+        // A file referred to only by a dropped metadatum has its source dropped too.
+        let dropped_id = source_engine.get_source_id(&std::path::PathBuf::from("b.sw"));
+        context
+            .metadata_sources
+            .insert(dropped_id, std::sync::Arc::from(""));
+        let dropped_file =
+            MetadataIndex::new_canonical(&mut context, Metadatum::SourceId(dropped_id));
+        let origin =
+            MetadataIndex::new_struct(&mut context, "origin", vec![Metadatum::Index(dropped_file)]);
+        let (_, nops) = new_test_nops(&mut context, &[outer, origin]);
+
+        let collapsed = context.keep_only_spans();
+
+        assert_eq!(collapsed.len(), 2);
+        assert_eq!(collapsed.get(&inner), Some(&span));
+        assert_eq!(collapsed.get(&outer), Some(&span));
+        assert!(!context.metadata.contains(inner.0));
+        assert!(!context.metadata.contains(outer.0));
+        assert_eq!(nops[0].get_metadata(&context), Some(span));
+        assert_eq!(nops[1].get_metadata(&context), None);
+        assert_eq!(context.metadata_sources.len(), 1);
+        assert!(!context.metadata_sources.contains_key(&dropped_id));
+
+        // The span's still canonical.
+        assert_eq!(new_test_span(&mut context, "a.sw", 3, 7), span);
+    }
+
+    #[test]
+    fn snapshot_and_restore() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let first = new_test_span(&mut context, "a.sw", 3, 7);
+        let snap = context.snapshot_metadata();
+        let second = new_test_span(&mut context, "b.sw", 20, 25);
+        assert!(context.metadata.contains(second.0));
+
+        context.restore_metadata(snap);
+        assert!(!context.metadata.contains(second.0));
+        assert_eq!(first.to_span(&context).unwrap().as_str(), "main");
+        assert!(context.common_file(&[first]).is_some());
+    }
+
+    #[test]
+    fn find_first() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span = new_test_span(&mut context, "a.sw", 3, 7);
+        let storage = MetadataIndex::new_struct(
+            &mut context,
+            "storage",
+            vec![Metadatum::String("writes".to_owned())],
+        );
+        let list = MetadataIndex::new_list(&mut context, vec![span, storage]);
+
+        let is_storage = |md: &Metadatum| md.unwrap_struct("storage", 1).is_some();
+        assert_eq!(
+            list.find_first(&context, is_storage),
+            Some(storage.get_content(&context))
+        );
+        assert_eq!(span.find_first(&context, is_storage), None);
+
+        // Nested lists aren't searched.
+        let outer = MetadataIndex::new_list(&mut context, vec![span, list]);
+        assert_eq!(outer.find_first(&context, is_storage), None);
+        assert!(outer
+            .find_first(&context, |md| matches!(md, Metadatum::List(_)))
+            .is_some());
+    }
+
+    #[test]
+    fn metadata_topo_order() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        // Create the span before its file, so that arena order alone would be wrong.
+        let placeholder = MetadataIndex::new_integer(&mut context, 0);
+        let span = MetadataIndex::new_struct(
+            &mut context,
+            "span",
+            vec![
+                Metadatum::Index(placeholder),
+                Metadatum::Integer(3),
+                Metadatum::Integer(7),
+            ],
+        );
+        let file = MetadataIndex::new_string(&mut context, "a.sw");
+        context.metadata[span.0] = Metadatum::Struct(
+            "span".to_owned(),
+            vec![
+                Metadatum::Index(file),
+                Metadatum::Integer(3),
+                Metadatum::Integer(7),
+            ],
+        );
+        let list = MetadataIndex::new_list(&mut context, vec![span, placeholder]);
+
+        let order = context.metadata_topo_order().unwrap();
+        let pos = |md_idx| order.iter().position(|idx| *idx == md_idx).unwrap();
+        assert_eq!(order.len(), 4);
+        assert!(pos(file) < pos(span));
+        assert!(pos(span) < pos(list));
+        assert!(pos(placeholder) < pos(list));
+
+        context.metadata[file.0] = Metadatum::Index(list);
+        assert!(context.metadata_topo_order().is_err());
+    }
 }
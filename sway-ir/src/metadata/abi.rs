@@ -0,0 +1,276 @@
+//! Metadata describing how values cross the contract ABI, for backends and SDK generators.
+
+use std::fmt;
+
+use crate::{
+    context::Context,
+    metadata::{MetadataIndex, Metadatum},
+    value::Value,
+};
+
+/// Which way a value crosses the contract ABI boundary.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum BoundaryKind {
+    Input,
+    Output,
+}
+
+impl fmt::Display for BoundaryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BoundaryKind::Input => "input",
+            BoundaryKind::Output => "output",
+        })
+    }
+}
+
+/// The calling convention of a function, which decides its prologue and epilogue.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CallConv {
+    Internal,
+    Contract,
+    Script,
+    Predicate,
+}
+
+impl fmt::Display for CallConv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CallConv::Internal => "internal",
+            CallConv::Contract => "contract",
+            CallConv::Script => "script",
+            CallConv::Predicate => "predicate",
+        })
+    }
+}
+
+impl MetadataIndex {
+    /// Record the version of the ABI encoding scheme used for an entry point argument or return
+    /// value.  There are only ever a few versions so each has a single shared index.
+    pub fn new_abi_encoding_version(context: &mut Context, version: u32) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "abi_encoding".to_owned(),
+                vec![Metadatum::Integer(version as u64)],
+            ),
+        )
+    }
+
+    pub fn to_abi_encoding_version(&self, context: &Context) -> Option<u32> {
+        self.find_struct(context, "abi_encoding", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+            .and_then(|version| u32::try_from(version).ok())
+    }
+
+    /// Record the visibility of a struct field, as only public fields are part of the ABI.
+    pub fn new_field_visibility<S: Into<String>>(
+        context: &mut Context,
+        field_name: S,
+        is_public: bool,
+    ) -> Self {
+        let visibility = if is_public { "public" } else { "private" };
+        MetadataIndex::new_struct(
+            context,
+            "field_visibility",
+            vec![
+                Metadatum::String(field_name.into()),
+                Metadatum::String(visibility.to_owned()),
+            ],
+        )
+    }
+
+    /// Return the field name and whether the field is public.
+    pub fn to_field_visibility<'a>(&self, context: &'a Context) -> Option<(&'a str, bool)> {
+        self.find_struct(context, "field_visibility", 2)
+            .and_then(|fields| {
+                let is_public = match fields[1].unwrap_string()? {
+                    "public" => true,
+                    "private" => false,
+                    _otherwise => return None,
+                };
+                Some((fields[0].unwrap_string()?, is_public))
+            })
+    }
+
+    /// Mark a value as crossing the contract ABI boundary, either decoded from the call data or
+    /// encoded into the return data.  There is a single shared index per kind.
+    pub fn new_abi_boundary(context: &mut Context, kind: BoundaryKind) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "abi_boundary".to_owned(),
+                vec![Metadatum::String(kind.to_string())],
+            ),
+        )
+    }
+
+    pub fn to_abi_boundary(&self, context: &Context) -> Option<BoundaryKind> {
+        self.find_struct(context, "abi_boundary", 1)
+            .and_then(|fields| match fields[0].unwrap_string()? {
+                "input" => Some(BoundaryKind::Input),
+                "output" => Some(BoundaryKind::Output),
+                _otherwise => None,
+            })
+    }
+
+    /// Record the calling convention of a function.  There is a single shared index per
+    /// convention.
+    pub fn new_call_convention(context: &mut Context, conv: CallConv) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "call_conv".to_owned(),
+                vec![Metadatum::String(conv.to_string())],
+            ),
+        )
+    }
+
+    pub fn call_convention(&self, context: &Context) -> Option<CallConv> {
+        self.find_struct(context, "call_conv", 1)
+            .and_then(|fields| match fields[0].unwrap_string()? {
+                "internal" => Some(CallConv::Internal),
+                "contract" => Some(CallConv::Contract),
+                "script" => Some(CallConv::Script),
+                "predicate" => Some(CallConv::Predicate),
+                _otherwise => None,
+            })
+    }
+}
+
+impl<'eng> Context<'eng> {
+    /// Return every value decoded from the call data.
+    pub fn abi_inputs(&self) -> Vec<Value> {
+        self.values_crossing_abi(BoundaryKind::Input)
+    }
+
+    /// Return every value encoded into the return data.
+    pub fn abi_outputs(&self) -> Vec<Value> {
+        self.values_crossing_abi(BoundaryKind::Output)
+    }
+
+    fn values_crossing_abi(&self, kind: BoundaryKind) -> Vec<Value> {
+        self.attached_metadata_sites()
+            .into_iter()
+            .filter(|(_, md_idx)| md_idx.to_abi_boundary(self) == Some(kind))
+            .filter_map(|(value, _)| value)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sway_types::SourceEngine;
+
+    use crate::{
+        constant::Constant,
+        context::Context,
+        metadata::{
+            combine,
+            span::tests::new_test_span,
+            tests::{check_round_trips, new_test_nops, RoundTrip},
+            BoundaryKind, CallConv, MetadataIndex,
+        },
+        value::Value,
+    };
+
+    #[test]
+    fn round_trips() {
+        let round_trips: [RoundTrip; 10] = [
+            (
+                |context| MetadataIndex::new_abi_encoding_version(context, 0),
+                |md_idx, context| md_idx.to_abi_encoding_version(context) == Some(0),
+            ),
+            (
+                |context| MetadataIndex::new_abi_encoding_version(context, 1),
+                |md_idx, context| md_idx.to_abi_encoding_version(context) == Some(1),
+            ),
+            (
+                |context| MetadataIndex::new_field_visibility(context, "owner", true),
+                |md_idx, context| md_idx.to_field_visibility(context) == Some(("owner", true)),
+            ),
+            (
+                |context| MetadataIndex::new_field_visibility(context, "nonce", false),
+                |md_idx, context| md_idx.to_field_visibility(context) == Some(("nonce", false)),
+            ),
+            (
+                |context| MetadataIndex::new_abi_boundary(context, BoundaryKind::Input),
+                |md_idx, context| md_idx.to_abi_boundary(context) == Some(BoundaryKind::Input),
+            ),
+            (
+                |context| MetadataIndex::new_abi_boundary(context, BoundaryKind::Output),
+                |md_idx, context| md_idx.to_abi_boundary(context) == Some(BoundaryKind::Output),
+            ),
+            (
+                |context| MetadataIndex::new_call_convention(context, CallConv::Internal),
+                |md_idx, context| md_idx.call_convention(context) == Some(CallConv::Internal),
+            ),
+            (
+                |context| MetadataIndex::new_call_convention(context, CallConv::Contract),
+                |md_idx, context| md_idx.call_convention(context) == Some(CallConv::Contract),
+            ),
+            (
+                |context| MetadataIndex::new_call_convention(context, CallConv::Script),
+                |md_idx, context| md_idx.call_convention(context) == Some(CallConv::Script),
+            ),
+            (
+                |context| MetadataIndex::new_call_convention(context, CallConv::Predicate),
+                |md_idx, context| md_idx.call_convention(context) == Some(CallConv::Predicate),
+            ),
+        ];
+        check_round_trips(&round_trips);
+    }
+
+    #[test]
+    fn shared_kinds() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        for version in [0, 1] {
+            assert_eq!(
+                MetadataIndex::new_abi_encoding_version(&mut context, version),
+                MetadataIndex::new_abi_encoding_version(&mut context, version)
+            );
+        }
+        for kind in [BoundaryKind::Input, BoundaryKind::Output] {
+            assert_eq!(
+                MetadataIndex::new_abi_boundary(&mut context, kind),
+                MetadataIndex::new_abi_boundary(&mut context, kind)
+            );
+        }
+        for conv in [
+            CallConv::Internal,
+            CallConv::Contract,
+            CallConv::Script,
+            CallConv::Predicate,
+        ] {
+            assert_eq!(
+                MetadataIndex::new_call_convention(&mut context, conv),
+                MetadataIndex::new_call_convention(&mut context, conv)
+            );
+        }
+        assert_eq!(CallConv::Predicate.to_string(), "predicate");
+    }
+
+    #[test]
+    fn abi_boundary() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let mut md_idcs = Vec::new();
+        for kind in [BoundaryKind::Input, BoundaryKind::Output] {
+            let md_idx = MetadataIndex::new_abi_boundary(&mut context, kind);
+            let span_md_idx = new_test_span(&mut context, "a.sw", 20, 21);
+            md_idcs.push(combine(&mut context, &Some(span_md_idx), &Some(md_idx)).unwrap());
+        }
+
+        // Only values still in the IR are found.
+        let (block, nops) = new_test_nops(&mut context, &[md_idcs[0], md_idcs[1], md_idcs[0]]);
+        block.remove_instruction(&mut context, nops[2]);
+        let constant = Constant::new_uint(&mut context, 64, 0);
+        Value::new_constant(&mut context, constant).add_metadatum(&mut context, Some(md_idcs[0]));
+
+        assert_eq!(context.abi_inputs(), vec![nops[0]]);
+        assert_eq!(context.abi_outputs(), vec![nops[1]]);
+    }
+}
@@ -0,0 +1,266 @@
+//! Metadata recording estimated costs, for passes and reports which rank code by expense.
+
+use crate::{
+    context::Context,
+    function::Function,
+    metadata::{MetadataIndex, Metadatum},
+};
+
+impl MetadataIndex {
+    /// Record the estimated VM gas cost of an instruction.
+    pub fn new_gas_cost(context: &mut Context, gas: u64) -> Self {
+        MetadataIndex::new_struct(context, "gas_cost", vec![Metadatum::Integer(gas)])
+    }
+
+    pub fn to_gas_cost(&self, context: &Context) -> Option<u64> {
+        self.find_struct(context, "gas_cost", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+    }
+
+    /// Record the cyclomatic complexity of a function, typically combined with its span.
+    pub fn new_complexity(context: &mut Context, complexity: u32) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "complexity",
+            vec![Metadatum::Integer(complexity as u64)],
+        )
+    }
+
+    pub fn to_complexity(&self, context: &Context) -> Option<u32> {
+        self.find_struct(context, "complexity", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+            .and_then(|complexity| u32::try_from(complexity).ok())
+    }
+
+    /// Record the maximum recursion depth of a function, either declared or found by analysis.
+    /// A bound of `u32::MAX` means the depth is unbounded or unknown.
+    pub fn new_recursion_bound(context: &mut Context, max_depth: u32) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "recursion_bound",
+            vec![Metadatum::Integer(max_depth as u64)],
+        )
+    }
+
+    pub fn to_recursion_bound(&self, context: &Context) -> Option<u32> {
+        self.find_struct(context, "recursion_bound", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+            .and_then(|max_depth| u32::try_from(max_depth).ok())
+    }
+
+    /// Record the estimated size in bytes of the stack frame of a function.
+    pub fn new_stack_frame_size(context: &mut Context, bytes: u64) -> Self {
+        MetadataIndex::new_struct(context, "stack_frame_size", vec![Metadatum::Integer(bytes)])
+    }
+
+    pub fn to_stack_frame_size(&self, context: &Context) -> Option<u64> {
+        self.find_struct(context, "stack_frame_size", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+    }
+}
+
+impl<'eng> Context<'eng> {
+    /// Sum the gas costs of every instruction in every function, saturating rather than
+    /// overflowing.  Instructions which share their metadata, e.g., copies made by the inliner,
+    /// are each counted.
+    pub fn total_estimated_gas(&self) -> u64 {
+        self.functions
+            .iter()
+            .flat_map(|(idx, _)| Function(idx).instruction_iter(self))
+            .filter_map(|(_, ins_val)| ins_val.get_metadata(self))
+            .filter_map(|md_idx| md_idx.to_gas_cost(self))
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Find the metadata of every function whose recorded complexity is over the threshold.
+    pub fn functions_over_complexity(&self, threshold: u32) -> Vec<MetadataIndex> {
+        self.functions
+            .iter()
+            .filter_map(|(_, function)| function.metadata)
+            .filter(|md_idx| matches!(md_idx.to_complexity(self), Some(c) if c > threshold))
+            .collect()
+    }
+
+    /// Find the metadata of every function whose recursion depth is unbounded or unknown.
+    pub fn unbounded_recursive_functions(&self) -> Vec<MetadataIndex> {
+        self.functions
+            .iter()
+            .filter_map(|(_, function)| function.metadata)
+            .filter(|md_idx| md_idx.to_recursion_bound(self) == Some(u32::MAX))
+            .collect()
+    }
+
+    /// Find the metadata of every function whose estimated stack frame is over `bytes` in size.
+    pub fn functions_over_stack_size(&self, bytes: u64) -> Vec<MetadataIndex> {
+        self.functions
+            .iter()
+            .filter_map(|(_, function)| function.metadata)
+            .filter(|md_idx| matches!(md_idx.to_stack_frame_size(self), Some(size) if size > bytes))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sway_types::SourceEngine;
+
+    use crate::{
+        context::Context,
+        function::Function,
+        irtype::Type,
+        metadata::{
+            combine,
+            span::tests::new_test_span,
+            tests::{check_round_trips, RoundTrip},
+            MetadataIndex,
+        },
+        module::{Kind, Module},
+    };
+
+    /// Create a function with a span combined with `md_idx`, returning its metadata.
+    fn new_test_fn(
+        context: &mut Context,
+        module: Module,
+        name: &str,
+        md_idx: MetadataIndex,
+    ) -> MetadataIndex {
+        let span_md_idx = new_test_span(context, "a.sw", 3, 7);
+        let md_idx = combine(context, &Some(span_md_idx), &Some(md_idx));
+        let unit_ty = Type::get_unit(context);
+        Function::new(
+            context,
+            module,
+            name.to_owned(),
+            Vec::new(),
+            unit_ty,
+            None,
+            false,
+            false,
+            md_idx,
+        );
+        md_idx.unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let round_trips: [RoundTrip; 6] = [
+            (
+                |context| MetadataIndex::new_gas_cost(context, 12),
+                |md_idx, context| md_idx.to_gas_cost(context) == Some(12),
+            ),
+            (
+                |context| MetadataIndex::new_complexity(context, 23),
+                |md_idx, context| md_idx.to_complexity(context) == Some(23),
+            ),
+            (
+                |context| MetadataIndex::new_recursion_bound(context, 16),
+                |md_idx, context| md_idx.to_recursion_bound(context) == Some(16),
+            ),
+            (
+                |context| MetadataIndex::new_recursion_bound(context, u32::MAX),
+                |md_idx, context| md_idx.to_recursion_bound(context) == Some(u32::MAX),
+            ),
+            (
+                |context| MetadataIndex::new_stack_frame_size(context, 64),
+                |md_idx, context| md_idx.to_stack_frame_size(context) == Some(64),
+            ),
+            (
+                |context| MetadataIndex::new_stack_frame_size(context, 4096),
+                |md_idx, context| md_idx.to_stack_frame_size(context) == Some(4096),
+            ),
+        ];
+        check_round_trips(&round_trips);
+    }
+
+    #[test]
+    fn total_estimated_gas() {
+        let source_engine = SourceEngine::default();
+        let mut context = crate::parse(
+            r#"script {
+    entry fn main() -> () {
+        entry():
+        v0 = nop, !0
+        v1 = nop, !0
+        v2 = nop, !1
+        v3 = const unit ()
+        ret () v3
+    }
+}
+
+!0 = gas_cost 10
+!1 = gas_cost 100
+"#,
+            &source_engine,
+        )
+        .unwrap();
+        // Both instructions sharing a cost are counted.
+        assert_eq!(context.total_estimated_gas(), 120);
+
+        // Detached costs aren't counted.
+        MetadataIndex::new_gas_cost(&mut context, 1000);
+        assert_eq!(context.total_estimated_gas(), 120);
+
+        let function = context
+            .module_iter()
+            .next()
+            .unwrap()
+            .function_iter(&context)
+            .next()
+            .unwrap();
+        let block = function.get_entry_block(&context);
+        let instrs = block.instruction_iter(&context).collect::<Vec<_>>();
+        block.remove_instruction(&mut context, instrs[2]);
+        assert_eq!(context.total_estimated_gas(), 20);
+
+        let max_md_idx = MetadataIndex::new_gas_cost(&mut context, u64::MAX);
+        instrs[3].add_metadatum(&mut context, Some(max_md_idx));
+        assert_eq!(context.total_estimated_gas(), u64::MAX);
+    }
+
+    #[test]
+    fn functions_over_complexity() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+        let module = Module::new(&mut context, Kind::Script);
+
+        let simple = MetadataIndex::new_complexity(&mut context, 3);
+        new_test_fn(&mut context, module, "simple", simple);
+        let complex = MetadataIndex::new_complexity(&mut context, 23);
+        let complex = new_test_fn(&mut context, module, "complex", complex);
+
+        assert_eq!(context.functions_over_complexity(20), vec![complex]);
+        assert_eq!(context.functions_over_complexity(23), vec![]);
+        assert_eq!(context.functions_over_complexity(0).len(), 2);
+    }
+
+    #[test]
+    fn recursion_bound() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+        let module = Module::new(&mut context, Kind::Script);
+
+        let bounded = MetadataIndex::new_recursion_bound(&mut context, 16);
+        new_test_fn(&mut context, module, "bounded", bounded);
+        assert!(context.unbounded_recursive_functions().is_empty());
+
+        let unbounded = MetadataIndex::new_recursion_bound(&mut context, u32::MAX);
+        let unbounded = new_test_fn(&mut context, module, "unbounded", unbounded);
+        assert_eq!(context.unbounded_recursive_functions(), vec![unbounded]);
+    }
+
+    #[test]
+    fn stack_frame_size() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+        let module = Module::new(&mut context, Kind::Script);
+
+        let small = MetadataIndex::new_stack_frame_size(&mut context, 64);
+        new_test_fn(&mut context, module, "small", small);
+        let large = MetadataIndex::new_stack_frame_size(&mut context, 4096);
+        let large = new_test_fn(&mut context, module, "large", large);
+
+        assert_eq!(context.functions_over_stack_size(1024), vec![large]);
+        assert_eq!(context.functions_over_stack_size(32).len(), 2);
+        assert!(context.functions_over_stack_size(4096).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+//! Metadata carrying source attributes which direct how passes and backends must treat code.
+
+use std::fmt;
+
+use crate::{
+    context::Context,
+    metadata::{MetadataIndex, Metadatum},
+};
+
+/// An inlining directive, from `#[inline(..)]`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum InlineHint {
+    Never,
+    Always,
+    #[default]
+    Default,
+}
+
+impl fmt::Display for InlineHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            InlineHint::Never => "inline(never)",
+            InlineHint::Always => "inline(always)",
+            InlineHint::Default => "inline",
+        })
+    }
+}
+
+/// How an arithmetic operation handles overflow.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ArithmeticMode {
+    Checked,
+    Wrapping,
+    Saturating,
+}
+
+impl fmt::Display for ArithmeticMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArithmeticMode::Checked => "checked",
+            ArithmeticMode::Wrapping => "wrapping",
+            ArithmeticMode::Saturating => "saturating",
+        })
+    }
+}
+
+impl MetadataIndex {
+    /// Record the inlining directive for a function.  There is a single shared index per
+    /// directive.
+    pub fn new_inline_directive(context: &mut Context, hint: InlineHint) -> Self {
+        let field = match hint {
+            InlineHint::Never => "never",
+            InlineHint::Always => "always",
+            InlineHint::Default => "default",
+        };
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "inline".to_owned(),
+                vec![Metadatum::String(field.to_owned())],
+            ),
+        )
+    }
+
+    pub fn to_inline_directive(&self, context: &Context) -> Option<InlineHint> {
+        self.find_struct(context, "inline", 1).and_then(|fields| {
+            match fields[0].unwrap_string()? {
+                "never" => Some(InlineHint::Never),
+                "always" => Some(InlineHint::Always),
+                "default" => Some(InlineHint::Default),
+                _otherwise => None,
+            }
+        })
+    }
+
+    /// The inlining directive for a function, or [`InlineHint::Default`] if it has none.
+    pub fn inline_hint(&self, context: &Context) -> InlineHint {
+        self.to_inline_directive(context).unwrap_or_default()
+    }
+
+    /// Record how an arithmetic operation handles overflow.  There is a single shared index per
+    /// mode.
+    pub fn new_arith_mode(context: &mut Context, mode: ArithmeticMode) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "arith_mode".to_owned(),
+                vec![Metadatum::String(mode.to_string())],
+            ),
+        )
+    }
+
+    pub fn arith_mode(&self, context: &Context) -> Option<ArithmeticMode> {
+        self.find_struct(context, "arith_mode", 1)
+            .and_then(|fields| match fields[0].unwrap_string()? {
+                "checked" => Some(ArithmeticMode::Checked),
+                "wrapping" => Some(ArithmeticMode::Wrapping),
+                "saturating" => Some(ArithmeticMode::Saturating),
+                _otherwise => None,
+            })
+    }
+
+    /// Record the language edition a module was compiled under.  There are only ever a few
+    /// editions so each has a single shared index.
+    pub fn new_language_edition(context: &mut Context, edition: u16) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "edition".to_owned(),
+                vec![Metadatum::Integer(edition as u64)],
+            ),
+        )
+    }
+
+    pub fn to_language_edition(&self, context: &Context) -> Option<u16> {
+        self.find_struct(context, "edition", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+            .and_then(|edition| u16::try_from(edition).ok())
+    }
+}
+
+impl<'eng> Context<'eng> {
+    /// Return the language edition recorded on the first module which has one.
+    pub fn module_edition(&self) -> Option<u16> {
+        self.modules.iter().find_map(|(_, module)| {
+            module
+                .metadata
+                .and_then(|md_idx| md_idx.to_language_edition(self))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sway_types::SourceEngine;
+
+    use crate::{
+        context::Context,
+        metadata::{
+            combine,
+            span::tests::new_test_span,
+            tests::{check_round_trips, RoundTrip},
+            ArithmeticMode, InlineHint, MetadataIndex,
+        },
+        module::{Kind, Module},
+    };
+
+    #[test]
+    fn round_trips() {
+        let round_trips: [RoundTrip; 7] = [
+            (
+                |context| MetadataIndex::new_inline_directive(context, InlineHint::Never),
+                |md_idx, context| md_idx.to_inline_directive(context) == Some(InlineHint::Never),
+            ),
+            (
+                |context| MetadataIndex::new_inline_directive(context, InlineHint::Always),
+                |md_idx, context| md_idx.to_inline_directive(context) == Some(InlineHint::Always),
+            ),
+            (
+                |context| MetadataIndex::new_inline_directive(context, InlineHint::Default),
+                |md_idx, context| md_idx.to_inline_directive(context) == Some(InlineHint::Default),
+            ),
+            (
+                |context| MetadataIndex::new_arith_mode(context, ArithmeticMode::Checked),
+                |md_idx, context| md_idx.arith_mode(context) == Some(ArithmeticMode::Checked),
+            ),
+            (
+                |context| MetadataIndex::new_arith_mode(context, ArithmeticMode::Wrapping),
+                |md_idx, context| md_idx.arith_mode(context) == Some(ArithmeticMode::Wrapping),
+            ),
+            (
+                |context| MetadataIndex::new_arith_mode(context, ArithmeticMode::Saturating),
+                |md_idx, context| md_idx.arith_mode(context) == Some(ArithmeticMode::Saturating),
+            ),
+            (
+                |context| MetadataIndex::new_language_edition(context, 2021),
+                |md_idx, context| md_idx.to_language_edition(context) == Some(2021),
+            ),
+        ];
+        check_round_trips(&round_trips);
+    }
+
+    #[test]
+    fn shared_kinds() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        for hint in [InlineHint::Never, InlineHint::Always, InlineHint::Default] {
+            assert_eq!(
+                MetadataIndex::new_inline_directive(&mut context, hint),
+                MetadataIndex::new_inline_directive(&mut context, hint)
+            );
+        }
+        for mode in [
+            ArithmeticMode::Checked,
+            ArithmeticMode::Wrapping,
+            ArithmeticMode::Saturating,
+        ] {
+            assert_eq!(
+                MetadataIndex::new_arith_mode(&mut context, mode),
+                MetadataIndex::new_arith_mode(&mut context, mode)
+            );
+        }
+        assert_eq!(
+            MetadataIndex::new_language_edition(&mut context, 2021),
+            MetadataIndex::new_language_edition(&mut context, 2021)
+        );
+        assert_eq!(InlineHint::Never.to_string(), "inline(never)");
+        assert_eq!(InlineHint::Always.to_string(), "inline(always)");
+        assert_eq!(ArithmeticMode::Wrapping.to_string(), "wrapping");
+    }
+
+    #[test]
+    fn inline_hint_defaults() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 0, 28);
+        assert_eq!(span_md_idx.inline_hint(&context), InlineHint::Default);
+
+        for hint in [InlineHint::Never, InlineHint::Always, InlineHint::Default] {
+            let hint_md_idx = MetadataIndex::new_inline_directive(&mut context, hint);
+            let md_idx = combine(&mut context, &Some(span_md_idx), &Some(hint_md_idx)).unwrap();
+            assert_eq!(md_idx.inline_hint(&context), hint);
+        }
+    }
+
+    #[test]
+    fn language_edition() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let module = Module::new(&mut context, Kind::Contract);
+        assert_eq!(context.module_edition(), None);
+
+        let edition_md_idx = MetadataIndex::new_language_edition(&mut context, 2021);
+        let span_md_idx = new_test_span(&mut context, "a.sw", 0, 28);
+        module
+            .add_metadatum(&mut context, Some(span_md_idx))
+            .add_metadatum(&mut context, Some(edition_md_idx));
+        assert_eq!(context.module_edition(), Some(2021));
+    }
+}
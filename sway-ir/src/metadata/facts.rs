@@ -0,0 +1,367 @@
+//! Metadata recording facts which analysis passes have proven about values, so that later passes
+//! needn't recompute them.
+
+use std::fmt;
+
+use crate::{
+    context::Context,
+    error::IrError,
+    metadata::{MetadataIndex, Metadatum},
+};
+
+/// Whether an allocation may outlive the function which makes it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Escapes {
+    No,
+    Maybe,
+    Yes,
+}
+
+impl fmt::Display for Escapes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Escapes::No => "noescape",
+            Escapes::Maybe => "mayescape",
+            Escapes::Yes => "escapes",
+        })
+    }
+}
+
+impl MetadataIndex {
+    /// Record that an enum value is statically known to be a particular variant.
+    pub fn new_known_variant<E: Into<String>, V: Into<String>>(
+        context: &mut Context,
+        enum_name: E,
+        variant_name: V,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "known_variant",
+            vec![
+                Metadatum::String(enum_name.into()),
+                Metadatum::String(variant_name.into()),
+            ],
+        )
+    }
+
+    /// Return the enum and variant names of a known variant.
+    pub fn to_known_variant<'a>(&self, context: &'a Context) -> Option<(&'a str, &'a str)> {
+        self.find_struct(context, "known_variant", 2)
+            .and_then(|fields| Some((fields[0].unwrap_string()?, fields[1].unwrap_string()?)))
+    }
+
+    /// Record whether an allocation may outlive its function.  There is a single shared index per
+    /// status.
+    pub fn new_escape_status(context: &mut Context, escapes: Escapes) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "escape".to_owned(),
+                vec![Metadatum::String(escapes.to_string())],
+            ),
+        )
+    }
+
+    pub fn to_escape_status(&self, context: &Context) -> Option<Escapes> {
+        self.find_struct(context, "escape", 1).and_then(|fields| {
+            match fields[0].unwrap_string()? {
+                "noescape" => Some(Escapes::No),
+                "mayescape" => Some(Escapes::Maybe),
+                "escapes" => Some(Escapes::Yes),
+                _otherwise => None,
+            }
+        })
+    }
+
+    /// Record the immediate dominator of a value, by its stable id, and the value's depth in the
+    /// dominator tree, so that dominance needn't be recomputed.
+    ///
+    /// This is invalidated by any pass which changes the CFG, and it's up to those passes to
+    /// remove it.
+    pub fn new_dominator_info(
+        context: &mut Context,
+        idom_value_id: u64,
+        dom_tree_depth: u32,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "dominator",
+            vec![
+                Metadatum::Integer(idom_value_id),
+                Metadatum::Integer(dom_tree_depth as u64),
+            ],
+        )
+    }
+
+    /// Return the immediate dominator id and dominator tree depth of a value.
+    pub fn to_dominator_info(&self, context: &Context) -> Option<(u64, u32)> {
+        self.find_struct(context, "dominator", 2)
+            .and_then(|fields| {
+                Some((
+                    fields[0].unwrap_integer()?,
+                    u32::try_from(fields[1].unwrap_integer()?).ok()?,
+                ))
+            })
+    }
+
+    /// Record the statically known length of an array, so that bounds checks on it may be
+    /// proven redundant.
+    pub fn new_array_length(context: &mut Context, len: u64) -> Self {
+        MetadataIndex::new_struct(context, "array_length", vec![Metadatum::Integer(len)])
+    }
+
+    pub fn known_array_length(&self, context: &Context) -> Option<u64> {
+        self.find_struct(context, "array_length", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+    }
+
+    /// Label a value with where it was derived from for taint tracking, e.g., `untrusted`.  A
+    /// value may have several labels, combined in a list.
+    pub fn new_taint_label<S: Into<String>>(context: &mut Context, label: S) -> Self {
+        MetadataIndex::new_struct(context, "taint", vec![Metadatum::String(label.into())])
+    }
+
+    /// Return the first taint label of a value.
+    pub fn to_taint_label<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        self.find_struct(context, "taint", 1)
+            .and_then(|fields| fields[0].unwrap_string())
+    }
+
+    /// Whether a value has the taint `label`, searching nested lists too.
+    pub fn has_taint(&self, context: &Context, label: &str) -> bool {
+        match self.get_content(context) {
+            Metadatum::List(md_idcs) => md_idcs
+                .iter()
+                .any(|md_idx| md_idx.has_taint(context, label)),
+            md => matches!(
+                md.unwrap_struct("taint", 1).and_then(|fields| fields[0].unwrap_string()),
+                Some(l) if l == label
+            ),
+        }
+    }
+
+    /// Mark an instruction as unreachable, with the reason it was found to be, e.g., `after
+    /// diverging call`, so that it may be reported rather than silently removed.
+    pub fn new_unreachable<S: Into<String>>(context: &mut Context, reason: S) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "unreachable",
+            vec![Metadatum::String(reason.into())],
+        )
+    }
+
+    pub fn to_unreachable<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        self.find_struct(context, "unreachable", 1)
+            .and_then(|fields| fields[0].unwrap_string())
+    }
+
+    /// Record that a value is invariant in the loop with the given header, by the header's
+    /// stable id, so that it may be hoisted out of it.
+    ///
+    /// This is invalidated by any pass which changes the loop structure, and it's up to those
+    /// passes to remove it.
+    pub fn new_loop_invariant(context: &mut Context, loop_header_id: u64) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "loop_invariant",
+            vec![Metadatum::Integer(loop_header_id)],
+        )
+    }
+
+    /// Return the id of the header of the loop a value is invariant in.
+    pub fn to_loop_invariant(&self, context: &Context) -> Option<u64> {
+        self.find_struct(context, "loop_invariant", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+    }
+
+    /// Record the inclusive range an integer value is known to be within.  Metadata integers
+    /// are unsigned 64 bit, so to cover signed values too the bounds are stored as strings.
+    pub fn new_value_range(context: &mut Context, lo: i128, hi: i128) -> Result<Self, IrError> {
+        if lo > hi {
+            return Err(IrError::InvalidMetadatum(format!(
+                "Value range [{lo}, {hi}] is empty."
+            )));
+        }
+        Ok(MetadataIndex::new_struct(
+            context,
+            "value_range",
+            vec![
+                Metadatum::String(lo.to_string()),
+                Metadatum::String(hi.to_string()),
+            ],
+        ))
+    }
+
+    /// Return the inclusive lower and upper bounds of a value's known range.
+    pub fn known_range(&self, context: &Context) -> Option<(i128, i128)> {
+        self.find_struct(context, "value_range", 2)
+            .and_then(|fields| {
+                Some((
+                    fields[0].unwrap_string()?.parse().ok()?,
+                    fields[1].unwrap_string()?.parse().ok()?,
+                ))
+            })
+    }
+
+    /// Record the stable ids of values which a value must be scheduled after, for dependencies
+    /// which the IR doesn't otherwise express, e.g., storage ordering.
+    pub fn new_depends_on(context: &mut Context, value_ids: impl IntoIterator<Item = u64>) -> Self {
+        let fields = value_ids.into_iter().map(Metadatum::Integer).collect();
+        MetadataIndex::new_struct(context, "depends_on", fields)
+    }
+
+    /// Return the stable ids of the values a value depends on, if any.
+    pub fn dependencies(&self, context: &Context) -> Vec<u64> {
+        self.find_variadic_struct(context, "depends_on")
+            .map(|fields| {
+                fields
+                    .iter()
+                    .filter_map(Metadatum::unwrap_integer)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl<'eng> Context<'eng> {
+    /// Return the attached metadata and reason of every instruction marked unreachable.
+    pub fn unreachable_sites(&self) -> impl Iterator<Item = (MetadataIndex, &str)> {
+        self.attached_metadata()
+            .into_iter()
+            .filter_map(|md_idx| md_idx.to_unreachable(self).map(|reason| (md_idx, reason)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sway_types::SourceEngine;
+
+    use crate::{
+        context::Context,
+        metadata::{
+            combine,
+            span::tests::new_test_span,
+            tests::{check_round_trips, new_test_nops, RoundTrip},
+            Escapes, MetadataIndex,
+        },
+    };
+
+    #[test]
+    fn round_trips() {
+        let round_trips: [RoundTrip; 11] = [
+            (
+                |context| MetadataIndex::new_known_variant(context, "Option", "Some"),
+                |md_idx, context| md_idx.to_known_variant(context) == Some(("Option", "Some")),
+            ),
+            (
+                |context| MetadataIndex::new_escape_status(context, Escapes::No),
+                |md_idx, context| md_idx.to_escape_status(context) == Some(Escapes::No),
+            ),
+            (
+                |context| MetadataIndex::new_escape_status(context, Escapes::Maybe),
+                |md_idx, context| md_idx.to_escape_status(context) == Some(Escapes::Maybe),
+            ),
+            (
+                |context| MetadataIndex::new_escape_status(context, Escapes::Yes),
+                |md_idx, context| md_idx.to_escape_status(context) == Some(Escapes::Yes),
+            ),
+            (
+                |context| MetadataIndex::new_dominator_info(context, 42, 3),
+                |md_idx, context| md_idx.to_dominator_info(context) == Some((42, 3)),
+            ),
+            (
+                |context| MetadataIndex::new_array_length(context, 10),
+                |md_idx, context| md_idx.known_array_length(context) == Some(10),
+            ),
+            (
+                |context| MetadataIndex::new_taint_label(context, "untrusted"),
+                |md_idx, context| md_idx.to_taint_label(context) == Some("untrusted"),
+            ),
+            (
+                |context| MetadataIndex::new_unreachable(context, "after diverging call"),
+                |md_idx, context| md_idx.to_unreachable(context) == Some("after diverging call"),
+            ),
+            (
+                |context| MetadataIndex::new_loop_invariant(context, 5),
+                |md_idx, context| md_idx.to_loop_invariant(context) == Some(5),
+            ),
+            (
+                |context| {
+                    MetadataIndex::new_value_range(context, i64::MIN as i128, u64::MAX as i128)
+                        .unwrap()
+                },
+                |md_idx, context| {
+                    md_idx.known_range(context) == Some((i64::MIN as i128, u64::MAX as i128))
+                },
+            ),
+            (
+                |context| MetadataIndex::new_depends_on(context, [3, 9]),
+                |md_idx, context| md_idx.dependencies(context) == vec![3, 9],
+            ),
+        ];
+        check_round_trips(&round_trips);
+    }
+
+    #[test]
+    fn escape_status() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        for escapes in [Escapes::No, Escapes::Maybe, Escapes::Yes] {
+            assert_eq!(
+                MetadataIndex::new_escape_status(&mut context, escapes),
+                MetadataIndex::new_escape_status(&mut context, escapes)
+            );
+        }
+        assert_eq!(Escapes::Maybe.to_string(), "mayescape");
+    }
+
+    #[test]
+    fn taint_labels() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let untrusted_md_idx = MetadataIndex::new_taint_label(&mut context, "untrusted");
+        let calldata_md_idx = MetadataIndex::new_taint_label(&mut context, "calldata");
+        let md_idx = combine(&mut context, &Some(span_md_idx), &Some(untrusted_md_idx));
+        let md_idx = combine(&mut context, &md_idx, &Some(calldata_md_idx)).unwrap();
+
+        assert!(md_idx.has_taint(&context, "untrusted"));
+        assert!(md_idx.has_taint(&context, "calldata"));
+        assert!(!md_idx.has_taint(&context, "sanitized"));
+        assert_eq!(md_idx.to_taint_label(&context), Some("untrusted"));
+        assert!(!span_md_idx.has_taint(&context, "untrusted"));
+    }
+
+    #[test]
+    fn unreachable() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let dead_md_idx = MetadataIndex::new_unreachable(&mut context, "after diverging call");
+        let md_idx = combine(&mut context, &Some(span_md_idx), &Some(dead_md_idx)).unwrap();
+
+        // Each dead instruction is reported, even if they share their metadata, unless it's been
+        // removed.
+        let (block, nops) = new_test_nops(&mut context, &[md_idx, span_md_idx, md_idx, md_idx]);
+        block.remove_instruction(&mut context, nops[3]);
+        assert_eq!(
+            context.unreachable_sites().collect::<Vec<_>>(),
+            vec![
+                (md_idx, "after diverging call"),
+                (md_idx, "after diverging call")
+            ]
+        );
+    }
+
+    #[test]
+    fn value_range_bounds() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        assert!(MetadataIndex::new_value_range(&mut context, 1, 1).is_ok());
+        assert!(MetadataIndex::new_value_range(&mut context, 2, 1).is_err());
+    }
+}
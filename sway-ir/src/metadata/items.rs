@@ -0,0 +1,807 @@
+//! Metadata describing the source declarations that functions and other items were lowered from.
+
+use sway_types::Span;
+
+use crate::{
+    context::Context,
+    error::IrError,
+    function::Function,
+    metadata::{MetadataIndex, Metadatum},
+};
+
+impl MetadataIndex {
+    /// Record the names of the type parameters of a generic declaration, e.g., `["T", "U"]`.
+    pub fn new_generic_params<S: Into<String>>(
+        context: &mut Context,
+        names: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let fields = names
+            .into_iter()
+            .map(|name| Metadatum::String(name.into()))
+            .collect();
+        MetadataIndex::new_struct(context, "generic_params", fields)
+    }
+
+    pub fn to_generic_params<'a>(&self, context: &'a Context) -> Option<Vec<&'a str>> {
+        self.find_variadic_struct(context, "generic_params")
+            .and_then(|fields| fields.iter().map(Metadatum::unwrap_string).collect())
+    }
+
+    /// Record the package, and its version, which an item was defined in.
+    pub fn new_source_package<N: Into<String>, V: Into<String>>(
+        context: &mut Context,
+        name: N,
+        version: V,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "package",
+            vec![
+                Metadatum::String(name.into()),
+                Metadatum::String(version.into()),
+            ],
+        )
+    }
+
+    /// Return the name and version of the package an item was defined in.
+    pub fn to_source_package<'a>(&self, context: &'a Context) -> Option<(&'a str, &'a str)> {
+        self.find_struct(context, "package", 2)
+            .and_then(|fields| Some((fields[0].unwrap_string()?, fields[1].unwrap_string()?)))
+    }
+
+    /// Record the source name and position of a function parameter, attached to its argument.
+    pub fn new_param_info<S: Into<String>>(context: &mut Context, name: S, index: usize) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "param",
+            vec![
+                Metadatum::String(name.into()),
+                Metadatum::Integer(index as u64),
+            ],
+        )
+    }
+
+    /// Return the name and position of a function parameter.
+    pub fn to_param_info<'a>(&self, context: &'a Context) -> Option<(&'a str, usize)> {
+        self.find_struct(context, "param", 2).and_then(|fields| {
+            Some((
+                fields[0].unwrap_string()?,
+                fields[1].unwrap_integer()? as usize,
+            ))
+        })
+    }
+
+    /// Record the feature which enabled a conditionally compiled item.
+    pub fn new_feature_gate<S: Into<String>>(context: &mut Context, feature: S) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "feature_gate",
+            vec![Metadatum::String(feature.into())],
+        )
+    }
+
+    pub fn to_feature_gate<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        self.find_struct(context, "feature_gate", 1)
+            .and_then(|fields| fields[0].unwrap_string())
+    }
+
+    /// Record the qualified name of an associated constant, e.g., `Foo::MAX`, attached to its
+    /// value.
+    pub fn new_associated_const<S: Into<String>>(context: &mut Context, qualified_name: S) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "associated_const",
+            vec![Metadatum::String(qualified_name.into())],
+        )
+    }
+
+    pub fn to_associated_const<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        self.find_struct(context, "associated_const", 1)
+            .and_then(|fields| fields[0].unwrap_string())
+    }
+
+    /// Record the source name of the return type of a function, e.g., `()`.  Functions with the
+    /// same return type share a single index.
+    pub fn new_return_type<S: Into<String>>(context: &mut Context, ty_name: S) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "return_type".to_owned(),
+                vec![Metadatum::String(ty_name.into())],
+            ),
+        )
+    }
+
+    pub fn to_return_type<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        self.find_struct(context, "return_type", 1)
+            .and_then(|fields| fields[0].unwrap_string())
+    }
+
+    /// Record a hash of the body of a function, so that incremental builds can tell whether it
+    /// has changed.
+    pub fn new_body_hash(context: &mut Context, hash: [u8; 32]) -> Self {
+        MetadataIndex::new_struct(context, "body_hash", vec![Metadatum::new_b256(&hash)])
+    }
+
+    pub fn to_body_hash(&self, context: &Context) -> Option<[u8; 32]> {
+        self.find_struct(context, "body_hash", 1)
+            .and_then(|fields| fields[0].unwrap_b256())
+    }
+
+    /// Record the verbatim `where` clause of a generic declaration, along with its span, which
+    /// must be a span metadatum.  Unlike a single resolved bound this is the whole clause as
+    /// written.
+    pub fn new_where_clause<S: Into<String>>(
+        context: &mut Context,
+        text: S,
+        span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        MetadataIndex::new_text_with_span(context, "where_clause", text, span_md_idx)
+    }
+
+    /// Return the text and span of a `where` clause.
+    pub fn to_where_clause<'a>(&self, context: &'a Context) -> Result<(&'a str, Span), IrError> {
+        self.get_text_with_span(context, "where_clause", "a where clause")
+    }
+
+    /// Record the path of the module an item was declared in, e.g., `["std", "vec"]`.
+    pub fn new_module_path<S: Into<String>>(
+        context: &mut Context,
+        segments: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let fields = segments
+            .into_iter()
+            .map(|segment| Metadatum::String(segment.into()))
+            .collect();
+        MetadataIndex::new_struct(context, "module_path", fields)
+    }
+
+    pub fn to_module_path<'a>(&self, context: &'a Context) -> Option<Vec<&'a str>> {
+        self.find_variadic_struct(context, "module_path")
+            .and_then(|fields| fields.iter().map(Metadatum::unwrap_string).collect())
+    }
+
+    /// Return the fully qualified name of `item_name`, e.g., `std::vec::Vec`, if it has a
+    /// module path.
+    pub fn qualified_name(&self, context: &Context, item_name: &str) -> Option<String> {
+        self.to_module_path(context).map(|mut segments| {
+            segments.push(item_name);
+            segments.join("::")
+        })
+    }
+
+    /// Record where a re-exported item was defined and each of the places it was re-exported
+    /// from, all of which must be span metadata.
+    pub fn new_reexport_chain(
+        context: &mut Context,
+        definition_span_md_idx: MetadataIndex,
+        reexport_span_md_idcs: &[MetadataIndex],
+    ) -> Result<Self, IrError> {
+        definition_span_md_idx.verify_is_span(context)?;
+        for span_md_idx in reexport_span_md_idcs {
+            span_md_idx.verify_is_span(context)?;
+        }
+        let fields = std::iter::once(definition_span_md_idx)
+            .chain(reexport_span_md_idcs.iter().copied())
+            .map(Metadatum::Index)
+            .collect();
+        Ok(MetadataIndex::new_struct(context, "reexport_chain", fields))
+    }
+
+    /// Return the definition span and the re-export spans of a re-exported item.
+    pub fn to_reexport_chain(&self, context: &Context) -> Result<(Span, Vec<Span>), IrError> {
+        let invalid =
+            || IrError::InvalidMetadatum("Expected a re-export chain metadatum.".to_owned());
+        let (definition, reexports) = self
+            .find_variadic_struct(context, "reexport_chain")
+            .and_then(|fields| fields.split_first())
+            .ok_or_else(invalid)?;
+        let to_span =
+            |field: &Metadatum| field.unwrap_index().ok_or_else(invalid)?.to_span(context);
+        Ok((
+            to_span(definition)?,
+            reexports.iter().map(to_span).collect::<Result<_, _>>()?,
+        ))
+    }
+
+    /// Record that a method is the default body provided by the trait `trait_name`, rather than
+    /// one from the impl, along with the span of the default method, which must be a span
+    /// metadatum.
+    pub fn new_default_method<S: Into<String>>(
+        context: &mut Context,
+        trait_name: S,
+        method_span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        MetadataIndex::new_text_with_span(context, "default_method", trait_name, method_span_md_idx)
+    }
+
+    /// Return the trait name and default method span of a method using a trait default.
+    pub fn to_default_method<'a>(&self, context: &'a Context) -> Result<(&'a str, Span), IrError> {
+        self.get_text_with_span(context, "default_method", "a default method")
+    }
+
+    /// Record the value of a const generic argument, e.g., an array size, along with the span of
+    /// the expression it was computed from, which must be a span metadatum.
+    pub fn new_const_generic_arg<S: Into<String>>(
+        context: &mut Context,
+        param_name: S,
+        value: u64,
+        expr_span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        expr_span_md_idx.verify_is_span(context)?;
+        Ok(MetadataIndex::new_struct(
+            context,
+            "const_generic_arg",
+            vec![
+                Metadatum::String(param_name.into()),
+                Metadatum::Integer(value),
+                Metadatum::Index(expr_span_md_idx),
+            ],
+        ))
+    }
+
+    /// Return the parameter name, value and expression span of a const generic argument.
+    pub fn to_const_generic_arg<'a>(
+        &self,
+        context: &'a Context,
+    ) -> Result<(&'a str, u64, Span), IrError> {
+        let (param_name, value, span_md_idx) = self
+            .find_struct(context, "const_generic_arg", 3)
+            .and_then(|fields| {
+                Some((
+                    fields[0].unwrap_string()?,
+                    fields[1].unwrap_integer()?,
+                    fields[2].unwrap_index()?,
+                ))
+            })
+            .ok_or_else(|| {
+                IrError::InvalidMetadatum("Expected a const generic argument metadatum.".to_owned())
+            })?;
+        Ok((param_name, value, span_md_idx.to_span(context)?))
+    }
+
+    /// Record what each type parameter of a monomorphized item was bound to, as triples of the
+    /// parameter name, the concrete type name and the span of the type argument, which must be a
+    /// span metadatum.
+    pub fn new_type_bindings<P: Into<String>, T: Into<String>>(
+        context: &mut Context,
+        bindings: impl IntoIterator<Item = (P, T, MetadataIndex)>,
+    ) -> Result<Self, IrError> {
+        let mut fields = Vec::new();
+        for (param_name, ty_name, span_md_idx) in bindings {
+            span_md_idx.verify_is_span(context)?;
+            fields.push(Metadatum::String(param_name.into()));
+            fields.push(Metadatum::String(ty_name.into()));
+            fields.push(Metadatum::Index(span_md_idx));
+        }
+        Ok(MetadataIndex::new_struct(context, "type_bindings", fields))
+    }
+
+    /// Return the parameter name, concrete type name and type argument span of each binding.
+    pub fn to_type_bindings<'a>(
+        &self,
+        context: &'a Context,
+    ) -> Result<Vec<(&'a str, &'a str, Span)>, IrError> {
+        let invalid =
+            || IrError::InvalidMetadatum("Expected a type bindings metadatum.".to_owned());
+        let fields = self
+            .find_variadic_struct(context, "type_bindings")
+            .filter(|fields| fields.len() % 3 == 0)
+            .ok_or_else(invalid)?;
+        fields
+            .chunks(3)
+            .map(|binding| {
+                let param_name = binding[0].unwrap_string().ok_or_else(invalid)?;
+                let ty_name = binding[1].unwrap_string().ok_or_else(invalid)?;
+                let span = binding[2]
+                    .unwrap_index()
+                    .ok_or_else(invalid)?
+                    .to_span(context)?;
+                Ok((param_name, ty_name, span))
+            })
+            .collect()
+    }
+
+    /// Record the self type of the `impl` block a method was declared in, e.g., `Vec<u64>`.
+    /// Methods with the same self type share a single index.
+    pub fn new_self_type<S: Into<String>>(context: &mut Context, ty_name: S) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "self_type".to_owned(),
+                vec![Metadatum::String(ty_name.into())],
+            ),
+        )
+    }
+
+    pub fn to_self_type<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        self.find_struct(context, "self_type", 1)
+            .and_then(|fields| fields[0].unwrap_string())
+    }
+
+    /// Record a custom attribute of an item by name, along with its arguments as written, e.g.,
+    /// `bar = 1, baz`.
+    pub fn new_attribute_args<N: Into<String>, A: Into<String>>(
+        context: &mut Context,
+        name: N,
+        args: A,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "attribute",
+            vec![
+                Metadatum::String(name.into()),
+                Metadatum::String(args.into()),
+            ],
+        )
+    }
+
+    /// Return the name and verbatim arguments of a custom attribute.
+    pub fn to_attribute_args<'a>(&self, context: &'a Context) -> Option<(&'a str, &'a str)> {
+        self.find_struct(context, "attribute", 2)
+            .and_then(|fields| Some((fields[0].unwrap_string()?, fields[1].unwrap_string()?)))
+    }
+
+    /// Record where the type `type_name` was defined, by the span of its declaration, which must
+    /// be a span metadatum.
+    pub fn new_type_def<S: Into<String>>(
+        context: &mut Context,
+        type_name: S,
+        def_span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        MetadataIndex::new_text_with_span(context, "type_def", type_name, def_span_md_idx)
+    }
+
+    /// Return the type name and declaration span of a type definition.
+    pub fn to_type_def<'a>(&self, context: &'a Context) -> Result<(&'a str, Span), IrError> {
+        self.get_text_with_span(context, "type_def", "a type definition")
+    }
+
+    /// Record which trait, and for which concrete type, a vtable loaded for dynamic dispatch
+    /// belongs to.
+    pub fn new_vtable_origin<T: Into<String>, C: Into<String>>(
+        context: &mut Context,
+        trait_name: T,
+        concrete_type: C,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "vtable_origin",
+            vec![
+                Metadatum::String(trait_name.into()),
+                Metadatum::String(concrete_type.into()),
+            ],
+        )
+    }
+
+    /// Return the trait name and concrete type of a vtable.
+    pub fn to_vtable_origin<'a>(&self, context: &'a Context) -> Option<(&'a str, &'a str)> {
+        self.find_struct(context, "vtable_origin", 2)
+            .and_then(|fields| Some((fields[0].unwrap_string()?, fields[1].unwrap_string()?)))
+    }
+}
+
+impl<'eng> Context<'eng> {
+    /// Return the attached metadata of every item defined in the package `name`.
+    pub fn items_from_package(&self, name: &str) -> Vec<MetadataIndex> {
+        self.attached_metadata()
+            .into_iter()
+            .filter(|md_idx| {
+                matches!(md_idx.to_source_package(self), Some((pkg_name, _)) if pkg_name == name)
+            })
+            .collect()
+    }
+
+    /// Return the attached metadata of every item which was compiled in because of `feature`,
+    /// among any others it's gated on.
+    pub fn items_behind_feature(&self, feature: &str) -> Vec<MetadataIndex> {
+        self.attached_metadata()
+            .into_iter()
+            .filter(|md_idx| {
+                md_idx
+                    .find_structs(self, "feature_gate", 1)
+                    .iter()
+                    .any(|fields| fields[0].unwrap_string() == Some(feature))
+            })
+            .collect()
+    }
+
+    /// Return the attached metadata of the associated constant named `qualified_name`, if any.
+    pub fn find_associated_const(&self, qualified_name: &str) -> Option<MetadataIndex> {
+        self.attached_metadata()
+            .into_iter()
+            .find(|md_idx| md_idx.to_associated_const(self) == Some(qualified_name))
+    }
+
+    /// Return the recorded body hash of `function`, if it has one.
+    pub fn function_body_hash(&self, function: Function) -> Option<[u8; 32]> {
+        function
+            .get_metadata(self)
+            .and_then(|md_idx| md_idx.to_body_hash(self))
+    }
+
+    /// Return the attached metadata of every item with the custom attribute `name`, among any
+    /// others it has.
+    pub fn items_with_attribute(&self, name: &str) -> Vec<MetadataIndex> {
+        self.attached_metadata()
+            .into_iter()
+            .filter(|md_idx| {
+                md_idx
+                    .find_structs(self, "attribute", 2)
+                    .iter()
+                    .any(|fields| fields[0].unwrap_string() == Some(name))
+            })
+            .collect()
+    }
+
+    /// Return the attached metadata of the definition of the type `type_name`, if any.
+    pub fn type_definition(&self, type_name: &str) -> Option<MetadataIndex> {
+        self.attached_metadata().into_iter().find(|md_idx| {
+            md_idx
+                .find_struct(self, "type_def", 2)
+                .and_then(|fields| fields[0].unwrap_string())
+                == Some(type_name)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sway_types::SourceEngine;
+
+    use crate::{
+        context::Context,
+        function::Function,
+        irtype::Type,
+        metadata::{
+            combine,
+            span::tests::new_test_span,
+            tests::{check_round_trips, new_test_nops, RoundTrip},
+            MetadataIndex,
+        },
+        module::{Kind, Module},
+    };
+
+    const TEST_HASH: [u8; 32] = {
+        let mut hash = [0; 32];
+        hash[0] = 0xde;
+        hash[31] = 0xad;
+        hash
+    };
+
+    #[test]
+    fn round_trips() {
+        let round_trips: [RoundTrip; 20] = [
+            (
+                |context| MetadataIndex::new_generic_params(context, ["T", "U"]),
+                |md_idx, context| md_idx.to_generic_params(context) == Some(vec!["T", "U"]),
+            ),
+            (
+                |context| MetadataIndex::new_generic_params(context, Vec::<String>::new()),
+                |md_idx, context| md_idx.to_generic_params(context) == Some(vec![]),
+            ),
+            (
+                |context| MetadataIndex::new_source_package(context, "foo", "1.2.0"),
+                |md_idx, context| md_idx.to_source_package(context) == Some(("foo", "1.2.0")),
+            ),
+            (
+                |context| MetadataIndex::new_param_info(context, "amount", 1),
+                |md_idx, context| md_idx.to_param_info(context) == Some(("amount", 1)),
+            ),
+            (
+                |context| MetadataIndex::new_feature_gate(context, "experimental"),
+                |md_idx, context| md_idx.to_feature_gate(context) == Some("experimental"),
+            ),
+            (
+                |context| MetadataIndex::new_associated_const(context, "Foo::MAX"),
+                |md_idx, context| md_idx.to_associated_const(context) == Some("Foo::MAX"),
+            ),
+            (
+                |context| MetadataIndex::new_return_type(context, "()"),
+                |md_idx, context| md_idx.to_return_type(context) == Some("()"),
+            ),
+            (
+                |context| MetadataIndex::new_return_type(context, "Option<u64>"),
+                |md_idx, context| md_idx.to_return_type(context) == Some("Option<u64>"),
+            ),
+            (
+                |context| MetadataIndex::new_body_hash(context, TEST_HASH),
+                |md_idx, context| md_idx.to_body_hash(context) == Some(TEST_HASH),
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 20, 25);
+                    MetadataIndex::new_where_clause(context, "x = 1", span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_where_clause(context)
+                        .is_ok_and(|(text, span)| text == "x = 1" && span.as_str() == "x = 1")
+                },
+            ),
+            (
+                |context| MetadataIndex::new_module_path(context, ["std", "collections", "vec"]),
+                |md_idx, context| {
+                    md_idx.to_module_path(context) == Some(vec!["std", "collections", "vec"])
+                        && md_idx.qualified_name(context, "Vec").as_deref()
+                            == Some("std::collections::vec::Vec")
+                },
+            ),
+            (
+                |context| {
+                    let def_md_idx = new_test_span(context, "a.sw", 3, 7);
+                    let reexport_md_idcs = [
+                        new_test_span(context, "b.sw", 20, 21),
+                        new_test_span(context, "c.sw", 24, 25),
+                    ];
+                    MetadataIndex::new_reexport_chain(context, def_md_idx, &reexport_md_idcs)
+                        .unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_reexport_chain(context)
+                        .is_ok_and(|(definition, reexports)| {
+                            definition.as_str() == "main"
+                                && reexports.iter().map(|span| span.as_str()).eq(["x", "1"])
+                        })
+                },
+            ),
+            (
+                |context| {
+                    let method_span_md_idx = new_test_span(context, "b.sw", 3, 7);
+                    MetadataIndex::new_default_method(context, "Hash", method_span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_default_method(context)
+                        .is_ok_and(|(trait_name, method_span)| {
+                            trait_name == "Hash" && method_span.as_str() == "main"
+                        })
+                },
+            ),
+            (
+                |context| {
+                    let expr_span_md_idx = new_test_span(context, "a.sw", 24, 25);
+                    MetadataIndex::new_const_generic_arg(context, "N", 1, expr_span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    md_idx.to_const_generic_arg(context).is_ok_and(
+                        |(param_name, value, expr_span)| {
+                            param_name == "N" && value == 1 && expr_span.as_str() == "1"
+                        },
+                    )
+                },
+            ),
+            (
+                |context| {
+                    let t_span_md_idx = new_test_span(context, "a.sw", 3, 7);
+                    let u_span_md_idx = new_test_span(context, "a.sw", 24, 25);
+                    MetadataIndex::new_type_bindings(
+                        context,
+                        [("T", "u64", t_span_md_idx), ("U", "bool", u_span_md_idx)],
+                    )
+                    .unwrap()
+                },
+                |md_idx, context| {
+                    md_idx.to_type_bindings(context).is_ok_and(|bindings| {
+                        bindings
+                            .iter()
+                            .map(|(param, ty, span)| (*param, *ty, span.as_str()))
+                            .eq([("T", "u64", "main"), ("U", "bool", "1")])
+                    })
+                },
+            ),
+            (
+                |context| MetadataIndex::new_self_type(context, "Counter"),
+                |md_idx, context| md_idx.to_self_type(context) == Some("Counter"),
+            ),
+            (
+                |context| MetadataIndex::new_self_type(context, "Vec<u64>"),
+                |md_idx, context| md_idx.to_self_type(context) == Some("Vec<u64>"),
+            ),
+            (
+                |context| MetadataIndex::new_attribute_args(context, "foo", "bar = 1, baz"),
+                |md_idx, context| {
+                    md_idx.to_attribute_args(context) == Some(("foo", "bar = 1, baz"))
+                },
+            ),
+            (
+                |context| {
+                    let def_span_md_idx = new_test_span(context, "a.sw", 3, 7);
+                    MetadataIndex::new_type_def(context, "Point", def_span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_type_def(context)
+                        .is_ok_and(|(type_name, def_span)| {
+                            type_name == "Point" && def_span.as_str() == "main"
+                        })
+                },
+            ),
+            (
+                |context| MetadataIndex::new_vtable_origin(context, "Hash", "Point"),
+                |md_idx, context| md_idx.to_vtable_origin(context) == Some(("Hash", "Point")),
+            ),
+        ];
+        check_round_trips(&round_trips);
+    }
+
+    #[test]
+    fn shared_kinds() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        for ty_name in ["()", "Option<u64>"] {
+            assert_eq!(
+                MetadataIndex::new_return_type(&mut context, ty_name),
+                MetadataIndex::new_return_type(&mut context, ty_name)
+            );
+        }
+        for ty_name in ["Counter", "Vec<u64>"] {
+            assert_eq!(
+                MetadataIndex::new_self_type(&mut context, ty_name),
+                MetadataIndex::new_self_type(&mut context, ty_name)
+            );
+        }
+    }
+
+    #[test]
+    fn span_links_must_be_spans() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        // Not even a list containing a span will do.
+        let span_md_idx = new_test_span(&mut context, "a.sw", 3, 7);
+        let int_md_idx = MetadataIndex::new_integer(&mut context, 0);
+        let not_span_md_idx = combine(&mut context, &Some(span_md_idx), &Some(int_md_idx)).unwrap();
+        assert!(MetadataIndex::new_where_clause(&mut context, "T: Eq", not_span_md_idx).is_err());
+        assert!(MetadataIndex::new_reexport_chain(&mut context, not_span_md_idx, &[]).is_err());
+        assert!(
+            MetadataIndex::new_reexport_chain(&mut context, span_md_idx, &[not_span_md_idx])
+                .is_err()
+        );
+        assert!(MetadataIndex::new_default_method(&mut context, "Eq", not_span_md_idx).is_err());
+        assert!(
+            MetadataIndex::new_const_generic_arg(&mut context, "M", 2, not_span_md_idx).is_err()
+        );
+        assert!(
+            MetadataIndex::new_type_bindings(&mut context, [("T", "u64", not_span_md_idx)])
+                .is_err()
+        );
+        assert!(MetadataIndex::new_type_def(&mut context, "Line", not_span_md_idx).is_err());
+    }
+
+    #[test]
+    fn source_package() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 0, 28);
+        let pkg_md_idx = MetadataIndex::new_source_package(&mut context, "foo", "1.2.0");
+        let md_idx = combine(&mut context, &Some(span_md_idx), &Some(pkg_md_idx)).unwrap();
+        let other_md_idx = MetadataIndex::new_source_package(&mut context, "bar", "0.1.0");
+        new_test_nops(&mut context, &[md_idx, other_md_idx, span_md_idx]);
+
+        assert_eq!(context.items_from_package("foo"), vec![md_idx]);
+        assert_eq!(context.items_from_package("bar"), vec![other_md_idx]);
+        assert!(context.items_from_package("baz").is_empty());
+    }
+
+    #[test]
+    fn feature_gate() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 0, 28);
+        let gate_md_idx = MetadataIndex::new_feature_gate(&mut context, "experimental");
+        let md_idx = combine(&mut context, &Some(span_md_idx), &Some(gate_md_idx)).unwrap();
+        let other_md_idx = MetadataIndex::new_feature_gate(&mut context, "legacy");
+        let both_md_idx = combine(&mut context, &Some(md_idx), &Some(other_md_idx)).unwrap();
+        new_test_nops(
+            &mut context,
+            &[md_idx, other_md_idx, both_md_idx, span_md_idx],
+        );
+
+        assert_eq!(
+            context.items_behind_feature("experimental"),
+            vec![md_idx, both_md_idx]
+        );
+        assert_eq!(
+            context.items_behind_feature("legacy"),
+            vec![other_md_idx, both_md_idx]
+        );
+        assert!(context.items_behind_feature("nightly").is_empty());
+    }
+
+    #[test]
+    fn associated_const() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let const_md_idx = MetadataIndex::new_associated_const(&mut context, "Foo::MAX");
+        let md_idx = combine(&mut context, &Some(span_md_idx), &Some(const_md_idx)).unwrap();
+
+        // Only attached metadata are found.
+        assert_eq!(context.find_associated_const("Foo::MAX"), None);
+        new_test_nops(&mut context, &[md_idx]);
+        assert_eq!(context.find_associated_const("Foo::MAX"), Some(md_idx));
+        assert_eq!(context.find_associated_const("Foo::MIN"), None);
+    }
+
+    #[test]
+    fn body_hash() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+        let module = Module::new(&mut context, Kind::Contract);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 0, 28);
+        let hash_md_idx = MetadataIndex::new_body_hash(&mut context, TEST_HASH);
+        let md_idx = combine(&mut context, &Some(span_md_idx), &Some(hash_md_idx));
+
+        let mut new_fn = |name: &str, md_idx| {
+            let unit_ty = Type::get_unit(&context);
+            Function::new(
+                &mut context,
+                module,
+                name.to_owned(),
+                Vec::new(),
+                unit_ty,
+                None,
+                false,
+                false,
+                md_idx,
+            )
+        };
+        let hashed = new_fn("hashed", md_idx);
+        let plain = new_fn("plain", Some(span_md_idx));
+        let bare = new_fn("bare", None);
+
+        assert_eq!(context.function_body_hash(hashed), Some(TEST_HASH));
+        assert_eq!(context.function_body_hash(plain), None);
+        assert_eq!(context.function_body_hash(bare), None);
+    }
+
+    #[test]
+    fn attribute_args() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 0, 28);
+        let attr_md_idx = MetadataIndex::new_attribute_args(&mut context, "foo", "bar = 1, baz");
+        let md_idx = combine(&mut context, &Some(span_md_idx), &Some(attr_md_idx)).unwrap();
+        let other_md_idx = MetadataIndex::new_attribute_args(&mut context, "qux", "1");
+        let both_md_idx = combine(&mut context, &Some(md_idx), &Some(other_md_idx)).unwrap();
+        new_test_nops(
+            &mut context,
+            &[md_idx, other_md_idx, both_md_idx, span_md_idx],
+        );
+
+        assert_eq!(
+            context.items_with_attribute("foo"),
+            vec![md_idx, both_md_idx]
+        );
+        assert_eq!(
+            context.items_with_attribute("qux"),
+            vec![other_md_idx, both_md_idx]
+        );
+        assert!(context.items_with_attribute("bar").is_empty());
+    }
+
+    #[test]
+    fn type_def() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let def_span_md_idx = new_test_span(&mut context, "a.sw", 3, 7);
+        let md_idx = MetadataIndex::new_type_def(&mut context, "Point", def_span_md_idx).unwrap();
+
+        // Only attached metadata are found.
+        assert_eq!(context.type_definition("Point"), None);
+        let span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let attached_md_idx = combine(&mut context, &Some(span_md_idx), &Some(md_idx)).unwrap();
+        new_test_nops(&mut context, &[attached_md_idx]);
+        assert_eq!(context.type_definition("Point"), Some(attached_md_idx));
+        assert_eq!(context.type_definition("Line"), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! Field-less marker metadata, set by analysis passes and read by later transforms.
+//!
+//! Each marker is a struct with a tag and no fields, and has a single shared index per context,
+//! so marking many values with it doesn't grow the metadata.
+
+use crate::{
+    context::Context,
+    metadata::{MetadataIndex, Metadatum},
+};
+
+impl MetadataIndex {
+    fn new_marker(context: &mut Context, tag: &str) -> Self {
+        MetadataIndex::new_canonical(context, Metadatum::Struct(tag.to_owned(), Vec::new()))
+    }
+
+    /// Mark a value as not depending on contract storage, so it may be freely reordered across
+    /// storage reads and writes.  Where a storage attribute asserts the presence of an effect this
+    /// asserts the absence of one.
+    pub fn new_storage_independent(context: &mut Context) -> Self {
+        Self::new_marker(context, "storage_independent")
+    }
+
+    pub fn is_storage_independent(&self, context: &Context) -> bool {
+        self.has_kind(context, "storage_independent")
+    }
+
+    /// Mark a `call` as being in tail position, so the backend may emit a jump in place of a call
+    /// and return.
+    pub fn new_tail_call(context: &mut Context) -> Self {
+        Self::new_marker(context, "tail_call")
+    }
+
+    pub fn is_tail_call(&self, context: &Context) -> bool {
+        self.has_kind(context, "tail_call")
+    }
+
+    /// Mark a value as requiring constant time execution, e.g., from a `#[constant_time]`
+    /// attribute, so that branching on it may be flagged.
+    pub fn new_constant_time(context: &mut Context) -> Self {
+        Self::new_marker(context, "constant_time")
+    }
+
+    pub fn requires_constant_time(&self, context: &Context) -> bool {
+        self.has_kind(context, "constant_time")
+    }
+
+    /// Mark a value as pure, i.e., free of any observable effect such as accessing storage,
+    /// logging, reverting or calling another contract, so it may be hoisted or deduplicated.
+    /// This implies but is stronger than being storage independent.
+    pub fn new_pure(context: &mut Context) -> Self {
+        Self::new_marker(context, "pure")
+    }
+
+    pub fn is_pure(&self, context: &Context) -> bool {
+        self.has_kind(context, "pure")
+    }
+
+    /// Mark a reference counted value as having a single owner, so its reference count updates
+    /// may be elided.
+    pub fn new_uniquely_owned(context: &mut Context) -> Self {
+        Self::new_marker(context, "uniquely_owned")
+    }
+
+    pub fn is_uniquely_owned(&self, context: &Context) -> bool {
+        self.has_kind(context, "uniquely_owned")
+    }
+
+    /// Mark a value as volatile, i.e., having effects the optimizer can't see, so that it must
+    /// never be removed or merged with another.
+    pub fn new_volatile(context: &mut Context) -> Self {
+        Self::new_marker(context, "volatile")
+    }
+
+    pub fn is_volatile(&self, context: &Context) -> bool {
+        self.has_kind(context, "volatile")
+    }
+
+    /// Mark a value as an optimization barrier, e.g., from a `black_box`, whose result must be
+    /// treated as opaque.  Nothing enforces this; it's up to each pass to stop at such values.
+    pub fn new_opt_barrier(context: &mut Context) -> Self {
+        Self::new_marker(context, "opt_barrier")
+    }
+
+    pub fn is_opt_barrier(&self, context: &Context) -> bool {
+        self.has_kind(context, "opt_barrier")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        context::Context,
+        metadata::{combine, span::tests::new_test_span, MetadataIndex},
+    };
+    use sway_types::SourceEngine;
+
+    type Marker = (
+        fn(&mut Context) -> MetadataIndex,
+        fn(&MetadataIndex, &Context) -> bool,
+    );
+
+    const MARKERS: [Marker; 7] = [
+        (
+            MetadataIndex::new_storage_independent,
+            MetadataIndex::is_storage_independent,
+        ),
+        (MetadataIndex::new_tail_call, MetadataIndex::is_tail_call),
+        (
+            MetadataIndex::new_constant_time,
+            MetadataIndex::requires_constant_time,
+        ),
+        (MetadataIndex::new_pure, MetadataIndex::is_pure),
+        (
+            MetadataIndex::new_uniquely_owned,
+            MetadataIndex::is_uniquely_owned,
+        ),
+        (MetadataIndex::new_volatile, MetadataIndex::is_volatile),
+        (
+            MetadataIndex::new_opt_barrier,
+            MetadataIndex::is_opt_barrier,
+        ),
+    ];
+
+    #[test]
+    fn markers() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 0, 2);
+        for (marker_idx, (new_marker, _)) in MARKERS.iter().enumerate() {
+            let marker_md_idx = new_marker(&mut context);
+            assert_eq!(marker_md_idx, new_marker(&mut context));
+
+            // Each marker is found alongside a span, and is the only one found.
+            let md_idx = combine(&mut context, &Some(span_md_idx), &Some(marker_md_idx)).unwrap();
+            for (pred_idx, (_, is_marked)) in MARKERS.iter().enumerate() {
+                assert_eq!(is_marked(&marker_md_idx, &context), pred_idx == marker_idx);
+                assert_eq!(is_marked(&md_idx, &context), pred_idx == marker_idx);
+            }
+        }
+        assert!(MARKERS
+            .iter()
+            .all(|(_, is_marked)| !is_marked(&span_md_idx, &context)));
+    }
+}
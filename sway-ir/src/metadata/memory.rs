@@ -0,0 +1,188 @@
+//! Metadata describing memory accesses and allocations, for alias analysis and memory dumps.
+
+use crate::{
+    context::Context,
+    error::IrError,
+    metadata::{MetadataIndex, Metadatum},
+};
+
+/// The kind of a source aggregate type.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AggregateKind {
+    Struct,
+    Enum,
+    Tuple,
+}
+
+impl AggregateKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AggregateKind::Struct => "struct",
+            AggregateKind::Enum => "enum",
+            AggregateKind::Tuple => "tuple",
+        }
+    }
+}
+
+impl MetadataIndex {
+    /// Record that a load or store accesses the named field of a struct, at `offset` bytes.
+    pub fn new_field_access<S: Into<String>, F: Into<String>>(
+        context: &mut Context,
+        struct_name: S,
+        field_name: F,
+        offset: u64,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "field_access",
+            vec![
+                Metadatum::String(struct_name.into()),
+                Metadatum::String(field_name.into()),
+                Metadatum::Integer(offset),
+            ],
+        )
+    }
+
+    /// Return the struct name, field name and offset of a field access.
+    pub fn to_field_access<'a>(&self, context: &'a Context) -> Option<(&'a str, &'a str, u64)> {
+        self.find_struct(context, "field_access", 3)
+            .and_then(|fields| {
+                Some((
+                    fields[0].unwrap_string()?,
+                    fields[1].unwrap_string()?,
+                    fields[2].unwrap_integer()?,
+                ))
+            })
+    }
+
+    /// Record the source level discriminant of an enum variant, explicit or auto-assigned,
+    /// typically attached to the instruction which sets the tag of a lowered enum.
+    pub fn new_enum_discriminant<E: Into<String>, V: Into<String>>(
+        context: &mut Context,
+        enum_name: E,
+        variant_name: V,
+        value: u64,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "enum_discriminant",
+            vec![
+                Metadatum::String(enum_name.into()),
+                Metadatum::String(variant_name.into()),
+                Metadatum::Integer(value),
+            ],
+        )
+    }
+
+    /// Return the enum name, variant name and discriminant value of an enum discriminant.
+    pub fn to_enum_discriminant<'a>(
+        &self,
+        context: &'a Context,
+    ) -> Option<(&'a str, &'a str, u64)> {
+        self.find_struct(context, "enum_discriminant", 3)
+            .and_then(|fields| {
+                Some((
+                    fields[0].unwrap_string()?,
+                    fields[1].unwrap_string()?,
+                    fields[2].unwrap_integer()?,
+                ))
+            })
+    }
+
+    /// Record an explicit alignment requirement, e.g., from an `#[align(N)]` attribute, which
+    /// must be a power of two.  Unlike inferred layout facts this must be honoured.
+    pub fn new_required_align(context: &mut Context, align: u64) -> Result<Self, IrError> {
+        if !align.is_power_of_two() {
+            return Err(IrError::InvalidMetadatum(format!(
+                "Required alignment {align} is not a power of two."
+            )));
+        }
+        Ok(MetadataIndex::new_struct(
+            context,
+            "required_align",
+            vec![Metadatum::Integer(align)],
+        ))
+    }
+
+    pub fn to_required_align(&self, context: &Context) -> Option<u64> {
+        self.find_struct(context, "required_align", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+    }
+
+    /// Record the number of fields, variants or elements a source aggregate type has, so that
+    /// its lowered layout may be checked against it.
+    pub fn new_field_count(context: &mut Context, count: u32, kind: AggregateKind) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "field_count",
+            vec![
+                Metadatum::Integer(count as u64),
+                Metadatum::String(kind.as_str().to_owned()),
+            ],
+        )
+    }
+
+    /// Return the field count and kind of an aggregate.
+    pub fn to_field_count(&self, context: &Context) -> Option<(u32, AggregateKind)> {
+        self.find_struct(context, "field_count", 2)
+            .and_then(|fields| {
+                let count = u32::try_from(fields[0].unwrap_integer()?).ok()?;
+                let kind = match fields[1].unwrap_string()? {
+                    "struct" => AggregateKind::Struct,
+                    "enum" => AggregateKind::Enum,
+                    "tuple" => AggregateKind::Tuple,
+                    _otherwise => return None,
+                };
+                Some((count, kind))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sway_types::SourceEngine;
+
+    use crate::{
+        context::Context,
+        metadata::{
+            tests::{check_round_trips, RoundTrip},
+            AggregateKind, MetadataIndex,
+        },
+    };
+
+    #[test]
+    fn round_trips() {
+        let round_trips: [RoundTrip; 4] = [
+            (
+                |context| MetadataIndex::new_field_access(context, "Point", "y", 8),
+                |md_idx, context| md_idx.to_field_access(context) == Some(("Point", "y", 8)),
+            ),
+            (
+                |context| MetadataIndex::new_enum_discriminant(context, "Color", "Blue", 7),
+                |md_idx, context| {
+                    md_idx.to_enum_discriminant(context) == Some(("Color", "Blue", 7))
+                },
+            ),
+            (
+                |context| MetadataIndex::new_required_align(context, 32).unwrap(),
+                |md_idx, context| md_idx.to_required_align(context) == Some(32),
+            ),
+            (
+                |context| MetadataIndex::new_field_count(context, 3, AggregateKind::Struct),
+                |md_idx, context| {
+                    md_idx.to_field_count(context) == Some((3, AggregateKind::Struct))
+                },
+            ),
+        ];
+        check_round_trips(&round_trips);
+    }
+
+    #[test]
+    fn required_align_power_of_two() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        assert!(MetadataIndex::new_required_align(&mut context, 24).is_err());
+        assert!(MetadataIndex::new_required_align(&mut context, 0).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! Tombstones recording why values were removed by optimizations, to explain removals in
+//! diagnostics.
+//!
+//! The tombstones are kept in their own table rather than attached to values, as the values they
+//! describe no longer exist.
+
+use crate::{context::Context, metadata::Metadatum};
+
+impl<'eng> Context<'eng> {
+    /// Record that the value with the stable `id` was removed by `pass`, and why.
+    pub fn record_removal<P: Into<String>, R: Into<String>>(
+        &mut self,
+        id: u64,
+        pass: P,
+        reason: R,
+    ) {
+        self.removed_values.insert(
+            id,
+            Metadatum::Struct(
+                "removed_by".to_owned(),
+                vec![
+                    Metadatum::String(pass.into()),
+                    Metadatum::String(reason.into()),
+                ],
+            ),
+        );
+    }
+
+    /// Return the pass which removed the value with the stable `id`, and why, if it was removed.
+    pub fn removal_reason(&self, id: u64) -> Option<(&str, &str)> {
+        self.removed_values
+            .get(&id)
+            .and_then(|md| md.unwrap_struct("removed_by", 2))
+            .and_then(|fields| Some((fields[0].unwrap_string()?, fields[1].unwrap_string()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sway_types::SourceEngine;
+
+    use crate::context::Context;
+
+    #[test]
+    fn removal_reason() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        context.record_removal(7, "dce", "result is never used");
+        assert_eq!(
+            context.removal_reason(7),
+            Some(("dce", "result is never used"))
+        );
+        assert_eq!(context.removal_reason(8), None);
+
+        context.record_removal(7, "constcombine", "folded into a constant");
+        assert_eq!(
+            context.removal_reason(7),
+            Some(("constcombine", "folded into a constant"))
+        );
+    }
+}
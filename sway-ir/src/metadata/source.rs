@@ -0,0 +1,1093 @@
+//! Metadata recording the source level form of expressions which is otherwise lost by lowering,
+//! for use in diagnostics and by the debugger.
+
+use sway_types::Span;
+
+use crate::{
+    context::Context,
+    error::IrError,
+    metadata::{MetadataIndex, Metadatum},
+    value::Value,
+};
+
+/// How a closure captures a variable.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CaptureMode {
+    ByValue,
+    ByRef,
+}
+
+impl CaptureMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CaptureMode::ByValue => "byvalue",
+            CaptureMode::ByRef => "byref",
+        }
+    }
+}
+
+impl MetadataIndex {
+    /// Record the rendered condition of an assertion, e.g., `x > 0`, along with the span of the
+    /// condition, which must be a span metadatum.
+    pub fn new_assertion_message<S: Into<String>>(
+        context: &mut Context,
+        text: S,
+        span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        MetadataIndex::new_text_with_span(context, "assertion", text, span_md_idx)
+    }
+
+    /// Return the condition text and span of an assertion message.
+    pub fn to_assertion<'a>(&self, context: &'a Context) -> Result<(&'a str, Span), IrError> {
+        self.get_text_with_span(context, "assertion", "an assertion")
+    }
+
+    /// Record which arm of a `match` a branch was lowered from, by its index and the span of its
+    /// pattern, which must be a span metadatum.
+    pub fn new_match_arm(
+        context: &mut Context,
+        arm_index: usize,
+        pattern_span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        pattern_span_md_idx.verify_is_span(context)?;
+        Ok(MetadataIndex::new_struct(
+            context,
+            "match_arm",
+            vec![
+                Metadatum::Integer(arm_index as u64),
+                Metadatum::Index(pattern_span_md_idx),
+            ],
+        ))
+    }
+
+    /// Return the arm index and pattern span of a match arm.
+    pub fn to_match_arm(&self, context: &Context) -> Result<(usize, Span), IrError> {
+        let (arm_index, span_md_idx) = self
+            .find_struct(context, "match_arm", 2)
+            .and_then(|fields| Some((fields[0].unwrap_integer()?, fields[1].unwrap_index()?)))
+            .ok_or_else(|| {
+                IrError::InvalidMetadatum("Expected a match arm metadatum.".to_owned())
+            })?;
+        Ok((arm_index as usize, span_md_idx.to_span(context)?))
+    }
+
+    /// Record the verbatim source text of a literal, e.g., `0xFF_u8`, so that diagnostics can
+    /// echo it exactly as written.
+    pub fn new_literal_formatting<S: Into<String>>(context: &mut Context, text: S) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "literal_formatting",
+            vec![Metadatum::String(text.into())],
+        )
+    }
+
+    pub fn to_literal_formatting<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        self.find_struct(context, "literal_formatting", 1)
+            .and_then(|fields| fields[0].unwrap_string())
+    }
+
+    /// Record the source type of a literal, e.g., `str`, typically attached to a constant.
+    pub fn new_literal_type<S: Into<String>>(context: &mut Context, ty_name: S) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "literal_type",
+            vec![Metadatum::String(ty_name.into())],
+        )
+    }
+
+    pub fn to_literal_type<'a>(&self, context: &'a Context) -> Option<&'a str> {
+        self.find_struct(context, "literal_type", 1)
+            .and_then(|fields| fields[0].unwrap_string())
+    }
+
+    /// Record the names of the fields of a struct literal in the order they were initialized in
+    /// the source, typically attached to the instruction which constructs the struct.
+    pub fn new_field_init_order<S: Into<String>>(
+        context: &mut Context,
+        field_names: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let fields = field_names
+            .into_iter()
+            .map(|name| Metadatum::String(name.into()))
+            .collect();
+        MetadataIndex::new_struct(context, "field_init_order", fields)
+    }
+
+    pub fn to_field_init_order<'a>(&self, context: &'a Context) -> Option<Vec<&'a str>> {
+        self.find_variadic_struct(context, "field_init_order")
+            .and_then(|fields| fields.iter().map(Metadatum::unwrap_string).collect())
+    }
+
+    /// Record the text of the source comment nearest an instruction, e.g., a `// FIXME`, along
+    /// with the span of the comment, which must be a span metadatum.  Unlike doc comments these
+    /// may be any comment preceding the instruction.
+    pub fn new_nearest_comment<S: Into<String>>(
+        context: &mut Context,
+        text: S,
+        span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        MetadataIndex::new_text_with_span(context, "nearest_comment", text, span_md_idx)
+    }
+
+    /// Return the text and span of the nearest comment.
+    pub fn to_nearest_comment<'a>(&self, context: &'a Context) -> Result<(&'a str, Span), IrError> {
+        self.get_text_with_span(context, "nearest_comment", "a nearest comment")
+    }
+
+    /// Record the source and destination widths, in bits, of a narrowing integer cast, which
+    /// must actually narrow.
+    pub fn new_original_width(
+        context: &mut Context,
+        from_bits: u32,
+        to_bits: u32,
+    ) -> Result<Self, IrError> {
+        if to_bits >= from_bits {
+            return Err(IrError::InvalidMetadatum(format!(
+                "Cast from {from_bits} to {to_bits} bits is not narrowing."
+            )));
+        }
+        Ok(MetadataIndex::new_struct(
+            context,
+            "original_width",
+            vec![
+                Metadatum::Integer(from_bits as u64),
+                Metadatum::Integer(to_bits as u64),
+            ],
+        ))
+    }
+
+    /// Return the source and destination widths of a narrowing cast.
+    pub fn to_original_width(&self, context: &Context) -> Option<(u32, u32)> {
+        self.find_struct(context, "original_width", 2)
+            .and_then(|fields| {
+                Some((
+                    u32::try_from(fields[0].unwrap_integer()?).ok()?,
+                    u32::try_from(fields[1].unwrap_integer()?).ok()?,
+                ))
+            })
+    }
+
+    /// Record the contents of a string literal at one of its uses, along with the span of the
+    /// use, which must be a span metadatum.  Literals are often pooled into a single constant so
+    /// this keeps the per-use locations, while the contents are shared between identical
+    /// literals.
+    pub fn new_string_literal<S: Into<String>>(
+        context: &mut Context,
+        content: S,
+        span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        span_md_idx.verify_is_span(context)?;
+        let content_md_idx =
+            MetadataIndex::new_canonical(context, Metadatum::String(content.into()));
+        Ok(MetadataIndex::new_struct(
+            context,
+            "string_literal",
+            vec![
+                Metadatum::Index(content_md_idx),
+                Metadatum::Index(span_md_idx),
+            ],
+        ))
+    }
+
+    /// Return the contents and use span of a string literal.
+    pub fn to_string_literal<'a>(&self, context: &'a Context) -> Result<(&'a str, Span), IrError> {
+        let (content, span_md_idx) = self
+            .find_struct(context, "string_literal", 2)
+            .and_then(|fields| {
+                let content = fields[0].unwrap_index()?.get_content(context);
+                Some((content.unwrap_string()?, fields[1].unwrap_index()?))
+            })
+            .ok_or_else(|| {
+                IrError::InvalidMetadatum("Expected a string literal metadatum.".to_owned())
+            })?;
+        Ok((content, span_md_idx.to_span(context)?))
+    }
+
+    /// Record the source and target type names of a coercion, and whether it was implicit or an
+    /// explicit cast.
+    pub fn new_coercion<F: Into<String>, T: Into<String>>(
+        context: &mut Context,
+        from_type: F,
+        to_type: T,
+        implicit: bool,
+    ) -> Self {
+        let kind = if implicit { "implicit" } else { "explicit" };
+        MetadataIndex::new_struct(
+            context,
+            "coercion",
+            vec![
+                Metadatum::String(from_type.into()),
+                Metadatum::String(to_type.into()),
+                Metadatum::String(kind.to_owned()),
+            ],
+        )
+    }
+
+    /// Return the source type, target type and whether a coercion was implicit.
+    pub fn to_coercion<'a>(&self, context: &'a Context) -> Option<(&'a str, &'a str, bool)> {
+        self.find_struct(context, "coercion", 3).and_then(|fields| {
+            let implicit = match fields[2].unwrap_string()? {
+                "implicit" => true,
+                "explicit" => false,
+                _otherwise => return None,
+            };
+            Some((
+                fields[0].unwrap_string()?,
+                fields[1].unwrap_string()?,
+                implicit,
+            ))
+        })
+    }
+
+    /// Record the justification given for an operation within an `unsafe` region, along with the
+    /// span of the justification, which must be a span metadatum.
+    pub fn new_unsafe_justification<S: Into<String>>(
+        context: &mut Context,
+        reason: S,
+        span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        MetadataIndex::new_text_with_span(context, "unsafe_justification", reason, span_md_idx)
+    }
+
+    /// Return the reason and span of an unsafe operation's justification.
+    pub fn to_unsafe_justification<'a>(
+        &self,
+        context: &'a Context,
+    ) -> Result<(&'a str, Span), IrError> {
+        self.get_text_with_span(context, "unsafe_justification", "an unsafe justification")
+    }
+
+    /// Record the spans of the argument expressions of a call, in order, each of which must be a
+    /// span metadatum.
+    pub fn new_call_args(
+        context: &mut Context,
+        arg_span_md_idcs: &[MetadataIndex],
+    ) -> Result<Self, IrError> {
+        for span_md_idx in arg_span_md_idcs {
+            span_md_idx.verify_is_span(context)?;
+        }
+        let fields = arg_span_md_idcs
+            .iter()
+            .map(|span_md_idx| Metadatum::Index(*span_md_idx))
+            .collect();
+        Ok(MetadataIndex::new_struct(context, "call_args", fields))
+    }
+
+    /// Return the spans of the argument expressions of a call.
+    pub fn to_call_arg_spans(&self, context: &Context) -> Result<Vec<Span>, IrError> {
+        self.find_variadic_struct(context, "call_args")
+            .ok_or_else(|| {
+                IrError::InvalidMetadatum("Expected a call arguments metadatum.".to_owned())
+            })?
+            .iter()
+            .map(|field| {
+                field
+                    .unwrap_index()
+                    .ok_or_else(|| {
+                        IrError::InvalidMetadatum("Expected a call argument span.".to_owned())
+                    })?
+                    .to_span(context)
+            })
+            .collect()
+    }
+
+    /// Record the base an integer literal was written in, which must be 2, 8, 10 or 16.  This is
+    /// lighter than [`MetadataIndex::new_literal_formatting`] for tools which only re-render the
+    /// value.
+    pub fn new_literal_base(context: &mut Context, base: u8) -> Result<Self, IrError> {
+        if !matches!(base, 2 | 8 | 10 | 16) {
+            return Err(IrError::InvalidMetadatum(format!(
+                "Invalid integer literal base {base}."
+            )));
+        }
+        Ok(MetadataIndex::new_struct(
+            context,
+            "literal_base",
+            vec![Metadatum::Integer(base as u64)],
+        ))
+    }
+
+    pub fn to_literal_base(&self, context: &Context) -> Option<u8> {
+        self.find_struct(context, "literal_base", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+            .and_then(|base| u8::try_from(base).ok())
+    }
+
+    /// Record whether an expression was explicitly parenthesized in the source.  There is a
+    /// single shared index for each case.
+    pub fn new_parenthesized(context: &mut Context, parenthesized: bool) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "parenthesized".to_owned(),
+                vec![Metadatum::Integer(parenthesized as u64)],
+            ),
+        )
+    }
+
+    pub fn to_parenthesized(&self, context: &Context) -> Option<bool> {
+        self.find_struct(context, "parenthesized", 1)
+            .and_then(|fields| match fields[0].unwrap_integer()? {
+                0 => Some(false),
+                1 => Some(true),
+                _otherwise => None,
+            })
+    }
+
+    /// Whether an expression was explicitly parenthesized, assuming not if it isn't recorded.
+    pub fn was_parenthesized(&self, context: &Context) -> bool {
+        self.to_parenthesized(context).unwrap_or(false)
+    }
+
+    /// Record the variables captured by a closure and how each is captured.
+    pub fn new_captures<S: Into<String>>(
+        context: &mut Context,
+        captures: impl IntoIterator<Item = (S, CaptureMode)>,
+    ) -> Self {
+        let fields = captures
+            .into_iter()
+            .flat_map(|(name, mode)| {
+                [
+                    Metadatum::String(name.into()),
+                    Metadatum::String(mode.as_str().to_owned()),
+                ]
+            })
+            .collect();
+        MetadataIndex::new_struct(context, "captures", fields)
+    }
+
+    /// Return the names and capture modes of the variables captured by a closure.
+    pub fn to_captures<'a>(&self, context: &'a Context) -> Option<Vec<(&'a str, CaptureMode)>> {
+        self.find_variadic_struct(context, "captures")
+            .filter(|fields| fields.len() % 2 == 0)
+            .and_then(|fields| {
+                fields
+                    .chunks(2)
+                    .map(|pair| {
+                        let mode = match pair[1].unwrap_string()? {
+                            "byvalue" => CaptureMode::ByValue,
+                            "byref" => CaptureMode::ByRef,
+                            _otherwise => return None,
+                        };
+                        Some((pair[0].unwrap_string()?, mode))
+                    })
+                    .collect()
+            })
+    }
+
+    /// Record the code a `revert` reverts with, and the reason given at its source, if any, so
+    /// an observed revert code can be traced back to where it came from.
+    pub fn new_revert_code(context: &mut Context, code: u64, reason: Option<String>) -> Self {
+        let fields = std::iter::once(Metadatum::Integer(code))
+            .chain(reason.map(Metadatum::String))
+            .collect();
+        MetadataIndex::new_struct(context, "revert_code", fields)
+    }
+
+    /// Return the code and optional reason of a revert.
+    pub fn to_revert_code<'a>(&self, context: &'a Context) -> Option<(u64, Option<&'a str>)> {
+        self.find_variadic_struct(context, "revert_code")
+            .and_then(|fields| match fields {
+                [code] => Some((code.unwrap_integer()?, None)),
+                [code, reason] => Some((code.unwrap_integer()?, Some(reason.unwrap_string()?))),
+                _otherwise => None,
+            })
+    }
+
+    /// Record the column at which a block's opening line was indented in the source, so that
+    /// reconstructed source may be indented the same way.
+    pub fn new_block_indent(context: &mut Context, column: u16) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "block_indent",
+            vec![Metadatum::Integer(column as u64)],
+        )
+    }
+
+    pub fn block_indent(&self, context: &Context) -> Option<u16> {
+        self.find_struct(context, "block_indent", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+            .and_then(|column| u16::try_from(column).ok())
+    }
+
+    /// Record that a type alias was used, e.g., `Addr`, and the type it resolved to, e.g.,
+    /// `b256`.
+    pub fn new_type_alias<A: Into<String>, R: Into<String>>(
+        context: &mut Context,
+        alias_name: A,
+        resolved_type: R,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "type_alias",
+            vec![
+                Metadatum::String(alias_name.into()),
+                Metadatum::String(resolved_type.into()),
+            ],
+        )
+    }
+
+    /// Return the alias name and resolved type of a type alias use.
+    pub fn to_type_alias<'a>(&self, context: &'a Context) -> Option<(&'a str, &'a str)> {
+        self.find_struct(context, "type_alias", 2)
+            .and_then(|fields| Some((fields[0].unwrap_string()?, fields[1].unwrap_string()?)))
+    }
+
+    /// Record the type suffix of an integer literal, e.g., `u8` for `5u8`, or `None` if it had
+    /// none.
+    pub fn new_literal_suffix(context: &mut Context, suffix: Option<String>) -> Self {
+        let fields = suffix.map(Metadatum::String).into_iter().collect();
+        MetadataIndex::new_struct(context, "literal_suffix", fields)
+    }
+
+    /// Return the suffix of a literal, which is `Some(None)` for a literal recorded as having no
+    /// suffix, or `None` if nothing was recorded.
+    pub fn literal_suffix<'a>(&self, context: &'a Context) -> Option<Option<&'a str>> {
+        self.find_variadic_struct(context, "literal_suffix")
+            .and_then(|fields| match fields {
+                [] => Some(None),
+                [suffix] => Some(Some(suffix.unwrap_string()?)),
+                _otherwise => None,
+            })
+    }
+
+    /// Record the template of a format style call, e.g., a `log`, along with the spans of each of
+    /// its interpolated arguments, which must be span metadata.
+    pub fn new_format_string<S: Into<String>>(
+        context: &mut Context,
+        template: S,
+        arg_span_md_idcs: &[MetadataIndex],
+    ) -> Result<Self, IrError> {
+        for span_md_idx in arg_span_md_idcs {
+            span_md_idx.verify_is_span(context)?;
+        }
+        let fields = std::iter::once(Metadatum::String(template.into()))
+            .chain(arg_span_md_idcs.iter().copied().map(Metadatum::Index))
+            .collect();
+        Ok(MetadataIndex::new_struct(context, "format_string", fields))
+    }
+
+    /// Return the template and argument spans of a format string.
+    pub fn to_format_string<'a>(
+        &self,
+        context: &'a Context,
+    ) -> Result<(&'a str, Vec<Span>), IrError> {
+        let invalid =
+            || IrError::InvalidMetadatum("Expected a format string metadatum.".to_owned());
+        let (template, args) = self
+            .find_variadic_struct(context, "format_string")
+            .and_then(|fields| fields.split_first())
+            .ok_or_else(invalid)?;
+        let template = template.unwrap_string().ok_or_else(invalid)?;
+        let arg_spans = args
+            .iter()
+            .map(|arg| arg.unwrap_index().ok_or_else(invalid)?.to_span(context))
+            .collect::<Result<_, _>>()?;
+        Ok((template, arg_spans))
+    }
+
+    /// Mark an instruction as produced by desugaring a `?` operator, by the span of the
+    /// operator, which must be a span metadatum.
+    pub fn new_try_operator(
+        context: &mut Context,
+        span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        span_md_idx.verify_is_span(context)?;
+        Ok(MetadataIndex::new_struct(
+            context,
+            "try_operator",
+            vec![Metadatum::Index(span_md_idx)],
+        ))
+    }
+
+    /// Return the span of the `?` operator an instruction was desugared from.
+    pub fn to_try_operator(&self, context: &Context) -> Result<Span, IrError> {
+        self.find_struct(context, "try_operator", 1)
+            .and_then(|fields| fields[0].unwrap_index())
+            .ok_or_else(|| {
+                IrError::InvalidMetadatum("Expected a try operator metadatum.".to_owned())
+            })?
+            .to_span(context)
+    }
+
+    /// Record that a call was synthesized to drop a value of type `dropped_type` when its scope
+    /// ends, along with the span of the end of the scope, which must be a span metadatum.
+    pub fn new_drop_glue<S: Into<String>>(
+        context: &mut Context,
+        dropped_type: S,
+        scope_end_span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        MetadataIndex::new_text_with_span(context, "drop_glue", dropped_type, scope_end_span_md_idx)
+    }
+
+    /// Return the dropped type and scope end span of a synthesized drop.
+    pub fn to_drop_glue<'a>(&self, context: &'a Context) -> Result<(&'a str, Span), IrError> {
+        self.get_text_with_span(context, "drop_glue", "a drop glue")
+    }
+
+    /// Mark an instruction as a compiler inserted bounds check, by the span of the indexing
+    /// expression it guards, which must be a span metadatum.
+    pub fn new_bounds_check(
+        context: &mut Context,
+        checked_span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        checked_span_md_idx.verify_is_span(context)?;
+        Ok(MetadataIndex::new_struct(
+            context,
+            "bounds_check",
+            vec![Metadatum::Index(checked_span_md_idx)],
+        ))
+    }
+
+    /// Return the span of the indexing expression a bounds check guards.
+    pub fn to_bounds_check(&self, context: &Context) -> Result<Span, IrError> {
+        self.find_struct(context, "bounds_check", 1)
+            .and_then(|fields| fields[0].unwrap_index())
+            .ok_or_else(|| {
+                IrError::InvalidMetadatum("Expected a bounds check metadatum.".to_owned())
+            })?
+            .to_span(context)
+    }
+}
+
+impl<'eng> Context<'eng> {
+    /// Return the attached metadata and justification of every unsafe operation.
+    pub fn unsafe_operations(&self) -> impl Iterator<Item = (MetadataIndex, &str)> {
+        self.attached_metadata().into_iter().filter_map(|md_idx| {
+            md_idx
+                .find_struct(self, "unsafe_justification", 2)
+                .and_then(|fields| fields[0].unwrap_string())
+                .map(|reason| (md_idx, reason))
+        })
+    }
+
+    /// Return the attached metadata, code and reason of every revert with a recorded code.
+    pub fn revert_sites(&self) -> impl Iterator<Item = (MetadataIndex, u64, Option<&str>)> {
+        self.attached_metadata().into_iter().filter_map(|md_idx| {
+            md_idx
+                .to_revert_code(self)
+                .map(|(code, reason)| (md_idx, code, reason))
+        })
+    }
+
+    /// Return the attached metadata and dropped type of every synthesized drop.
+    pub fn drop_sites(&self) -> impl Iterator<Item = (MetadataIndex, &str)> {
+        self.attached_metadata().into_iter().filter_map(|md_idx| {
+            md_idx
+                .find_struct(self, "drop_glue", 2)
+                .and_then(|fields| fields[0].unwrap_string())
+                .map(|dropped_type| (md_idx, dropped_type))
+        })
+    }
+
+    /// Return every instruction which is a compiler inserted bounds check, along with the span
+    /// it guards, or the error if that can't be resolved, e.g., if its source isn't available.
+    pub fn bounds_checks(&self) -> impl Iterator<Item = (Value, Result<Span, IrError>)> + '_ {
+        self.attached_metadata_sites()
+            .into_iter()
+            .filter_map(|(value, md_idx)| {
+                value
+                    .filter(|value| value.get_instruction(self).is_some())
+                    .filter(|_| md_idx.find_struct(self, "bounds_check", 1).is_some())
+                    .map(|value| (value, md_idx.to_bounds_check(self)))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sway_types::{SourceEngine, Span};
+
+    use crate::{
+        constant::Constant,
+        context::Context,
+        error::IrError,
+        instruction::Instruction,
+        irtype::Type,
+        metadata::{
+            combine,
+            span::tests::new_test_span,
+            tests::{check_round_trips, new_test_nops, RoundTrip},
+            CaptureMode, MetadataIndex,
+        },
+        value::Value,
+    };
+
+    fn is_text_with_span(found: Result<(&str, Span), IrError>, text: &str, span: &str) -> bool {
+        found
+            .is_ok_and(|(found_text, found_span)| found_text == text && found_span.as_str() == span)
+    }
+
+    fn are_spans(found: &[Span], spans: &[&str]) -> bool {
+        found
+            .iter()
+            .map(|span| span.as_str())
+            .eq(spans.iter().copied())
+    }
+
+    #[test]
+    fn round_trips() {
+        let round_trips: [RoundTrip; 31] = [
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 20, 25);
+                    MetadataIndex::new_assertion_message(context, "x = 1", span_md_idx).unwrap()
+                },
+                |md_idx, context| is_text_with_span(md_idx.to_assertion(context), "x = 1", "x = 1"),
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 24, 25);
+                    MetadataIndex::new_match_arm(context, 2, span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_match_arm(context)
+                        .is_ok_and(|(arm_index, span)| arm_index == 2 && span.as_str() == "1")
+                },
+            ),
+            (
+                |context| MetadataIndex::new_literal_formatting(context, "0xFF_u8"),
+                |md_idx, context| md_idx.to_literal_formatting(context) == Some("0xFF_u8"),
+            ),
+            (
+                |context| MetadataIndex::new_literal_formatting(context, "1_000_000"),
+                |md_idx, context| md_idx.to_literal_formatting(context) == Some("1_000_000"),
+            ),
+            (
+                |context| MetadataIndex::new_literal_type(context, "u64"),
+                |md_idx, context| md_idx.to_literal_type(context) == Some("u64"),
+            ),
+            (
+                |context| MetadataIndex::new_field_init_order(context, ["z", "x", "y"]),
+                |md_idx, context| md_idx.to_field_init_order(context) == Some(vec!["z", "x", "y"]),
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 3, 7);
+                    MetadataIndex::new_nearest_comment(context, "// FIXME", span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    is_text_with_span(md_idx.to_nearest_comment(context), "// FIXME", "main")
+                },
+            ),
+            (
+                |context| MetadataIndex::new_original_width(context, 64, 8).unwrap(),
+                |md_idx, context| md_idx.to_original_width(context) == Some((64, 8)),
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 3, 7);
+                    MetadataIndex::new_string_literal(context, "main", span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    is_text_with_span(md_idx.to_string_literal(context), "main", "main")
+                },
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 20, 25);
+                    MetadataIndex::new_string_literal(context, "main", span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    is_text_with_span(md_idx.to_string_literal(context), "main", "x = 1")
+                },
+            ),
+            (
+                |context| MetadataIndex::new_coercion(context, "u8", "u64", true),
+                |md_idx, context| md_idx.to_coercion(context) == Some(("u8", "u64", true)),
+            ),
+            (
+                |context| MetadataIndex::new_coercion(context, "u64", "u8", false),
+                |md_idx, context| md_idx.to_coercion(context) == Some(("u64", "u8", false)),
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 3, 7);
+                    MetadataIndex::new_unsafe_justification(
+                        context,
+                        "pointer is in bounds",
+                        span_md_idx,
+                    )
+                    .unwrap()
+                },
+                |md_idx, context| {
+                    is_text_with_span(
+                        md_idx.to_unsafe_justification(context),
+                        "pointer is in bounds",
+                        "main",
+                    )
+                },
+            ),
+            (
+                |context| {
+                    let arg_span_md_idcs = [
+                        new_test_span(context, "a.sw", 20, 21),
+                        new_test_span(context, "a.sw", 24, 25),
+                    ];
+                    MetadataIndex::new_call_args(context, &arg_span_md_idcs).unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_call_arg_spans(context)
+                        .is_ok_and(|spans| are_spans(&spans, &["x", "1"]))
+                },
+            ),
+            (
+                |context| MetadataIndex::new_literal_base(context, 2).unwrap(),
+                |md_idx, context| md_idx.to_literal_base(context) == Some(2),
+            ),
+            (
+                |context| MetadataIndex::new_literal_base(context, 8).unwrap(),
+                |md_idx, context| md_idx.to_literal_base(context) == Some(8),
+            ),
+            (
+                |context| MetadataIndex::new_literal_base(context, 10).unwrap(),
+                |md_idx, context| md_idx.to_literal_base(context) == Some(10),
+            ),
+            (
+                |context| MetadataIndex::new_literal_base(context, 16).unwrap(),
+                |md_idx, context| md_idx.to_literal_base(context) == Some(16),
+            ),
+            (
+                |context| MetadataIndex::new_parenthesized(context, true),
+                |md_idx, context| {
+                    md_idx.to_parenthesized(context) == Some(true)
+                        && md_idx.was_parenthesized(context)
+                },
+            ),
+            (
+                |context| MetadataIndex::new_parenthesized(context, false),
+                |md_idx, context| {
+                    md_idx.to_parenthesized(context) == Some(false)
+                        && !md_idx.was_parenthesized(context)
+                },
+            ),
+            (
+                |context| {
+                    MetadataIndex::new_captures(
+                        context,
+                        [
+                            ("count", CaptureMode::ByValue),
+                            ("buf", CaptureMode::ByRef),
+                            ("x", CaptureMode::ByValue),
+                        ],
+                    )
+                },
+                |md_idx, context| {
+                    md_idx.to_captures(context)
+                        == Some(vec![
+                            ("count", CaptureMode::ByValue),
+                            ("buf", CaptureMode::ByRef),
+                            ("x", CaptureMode::ByValue),
+                        ])
+                },
+            ),
+            (
+                |context| MetadataIndex::new_revert_code(context, 42, Some("not owner".to_owned())),
+                |md_idx, context| md_idx.to_revert_code(context) == Some((42, Some("not owner"))),
+            ),
+            (
+                |context| MetadataIndex::new_revert_code(context, 7, None),
+                |md_idx, context| md_idx.to_revert_code(context) == Some((7, None)),
+            ),
+            (
+                |context| MetadataIndex::new_block_indent(context, 4),
+                |md_idx, context| md_idx.block_indent(context) == Some(4),
+            ),
+            (
+                |context| MetadataIndex::new_type_alias(context, "Addr", "b256"),
+                |md_idx, context| md_idx.to_type_alias(context) == Some(("Addr", "b256")),
+            ),
+            (
+                |context| MetadataIndex::new_literal_suffix(context, Some("u8".to_owned())),
+                |md_idx, context| md_idx.literal_suffix(context) == Some(Some("u8")),
+            ),
+            (
+                |context| MetadataIndex::new_literal_suffix(context, None),
+                |md_idx, context| md_idx.literal_suffix(context) == Some(None),
+            ),
+            (
+                |context| {
+                    let arg_span_md_idcs = [
+                        new_test_span(context, "a.sw", 20, 21),
+                        new_test_span(context, "a.sw", 24, 25),
+                    ];
+                    MetadataIndex::new_format_string(context, "{} is {}", &arg_span_md_idcs)
+                        .unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_format_string(context)
+                        .is_ok_and(|(template, spans)| {
+                            template == "{} is {}" && are_spans(&spans, &["x", "1"])
+                        })
+                },
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 24, 25);
+                    MetadataIndex::new_try_operator(context, span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_try_operator(context)
+                        .is_ok_and(|span| span.as_str() == "1")
+                },
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 27, 28);
+                    MetadataIndex::new_drop_glue(context, "File", span_md_idx).unwrap()
+                },
+                |md_idx, context| is_text_with_span(md_idx.to_drop_glue(context), "File", "}"),
+            ),
+            (
+                |context| {
+                    let span_md_idx = new_test_span(context, "a.sw", 20, 25);
+                    MetadataIndex::new_bounds_check(context, span_md_idx).unwrap()
+                },
+                |md_idx, context| {
+                    md_idx
+                        .to_bounds_check(context)
+                        .is_ok_and(|span| span.as_str() == "x = 1")
+                },
+            ),
+        ];
+        check_round_trips(&round_trips);
+    }
+
+    #[test]
+    fn span_links_must_be_spans() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        // Not even a list containing a span will do.
+        let span_md_idx = new_test_span(&mut context, "a.sw", 3, 7);
+        let int_md_idx = MetadataIndex::new_integer(&mut context, 0);
+        let not_span_md_idx = combine(&mut context, &Some(span_md_idx), &Some(int_md_idx)).unwrap();
+        assert!(MetadataIndex::new_assertion_message(&mut context, "x", int_md_idx).is_err());
+        assert!(MetadataIndex::new_match_arm(&mut context, 0, not_span_md_idx).is_err());
+        assert!(
+            MetadataIndex::new_nearest_comment(&mut context, "// TODO", not_span_md_idx).is_err()
+        );
+        assert!(MetadataIndex::new_string_literal(&mut context, "", not_span_md_idx).is_err());
+        assert!(
+            MetadataIndex::new_unsafe_justification(&mut context, "", not_span_md_idx).is_err()
+        );
+        assert!(
+            MetadataIndex::new_call_args(&mut context, &[span_md_idx, not_span_md_idx]).is_err()
+        );
+        assert!(MetadataIndex::new_format_string(&mut context, "{}", &[not_span_md_idx]).is_err());
+        assert!(MetadataIndex::new_try_operator(&mut context, not_span_md_idx).is_err());
+        assert!(MetadataIndex::new_drop_glue(&mut context, "File", not_span_md_idx).is_err());
+        assert!(MetadataIndex::new_bounds_check(&mut context, not_span_md_idx).is_err());
+    }
+
+    #[test]
+    fn original_width_narrows() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        assert!(MetadataIndex::new_original_width(&mut context, 8, 64).is_err());
+        assert!(MetadataIndex::new_original_width(&mut context, 32, 32).is_err());
+    }
+
+    #[test]
+    fn literal_base_is_radix() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        assert!(MetadataIndex::new_literal_base(&mut context, 7).is_err());
+    }
+
+    #[test]
+    fn constant_literal_type() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 24, 25);
+        let ty_md_idx = MetadataIndex::new_literal_type(&mut context, "u64");
+        let constant = Constant::new_uint(&mut context, 64, 1);
+        Value::new_constant(&mut context, constant)
+            .add_metadatum(&mut context, Some(span_md_idx))
+            .add_metadatum(&mut context, Some(ty_md_idx));
+
+        // Metadata attached to instructions aren't included.
+        let other_ty_md_idx = MetadataIndex::new_literal_type(&mut context, "bool");
+        let unit = Constant::new_unit(&context);
+        let unit_val = Value::new_constant(&mut context, unit);
+        let unit_ty = Type::get_unit(&context);
+        Value::new_instruction(&mut context, Instruction::Ret(unit_val, unit_ty))
+            .add_metadatum(&mut context, Some(other_ty_md_idx));
+
+        let literal_types = context
+            .constant_metadata()
+            .filter_map(|(md_idx, _)| md_idx.to_literal_type(&context))
+            .collect::<Vec<_>>();
+        assert_eq!(literal_types, vec!["u64"]);
+        assert_eq!(context.constant_metadata().count(), 2);
+    }
+
+    #[test]
+    fn string_literal_contents_shared() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let first_span_md_idx = new_test_span(&mut context, "a.sw", 3, 7);
+        let second_span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let first_md_idx =
+            MetadataIndex::new_string_literal(&mut context, "main", first_span_md_idx).unwrap();
+        let second_md_idx =
+            MetadataIndex::new_string_literal(&mut context, "main", second_span_md_idx).unwrap();
+
+        let content_md_idcs = [first_md_idx, second_md_idx].map(|md_idx| {
+            md_idx
+                .get_content(&context)
+                .unwrap_struct("string_literal", 2)
+                .unwrap()[0]
+                .clone()
+        });
+        assert_eq!(content_md_idcs[0], content_md_idcs[1]);
+    }
+
+    #[test]
+    fn parenthesized_default() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 24, 25);
+        assert!(!span_md_idx.was_parenthesized(&context));
+    }
+
+    #[test]
+    fn unsafe_justification() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let reason_span_md_idx = new_test_span(&mut context, "a.sw", 3, 7);
+        let ins_span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let reason_md_idx = MetadataIndex::new_unsafe_justification(
+            &mut context,
+            "pointer is in bounds",
+            reason_span_md_idx,
+        )
+        .unwrap();
+        let md_idx = combine(&mut context, &Some(ins_span_md_idx), &Some(reason_md_idx)).unwrap();
+
+        // Operations sharing a justification are each listed, unless they've been removed.
+        let (block, nops) = new_test_nops(&mut context, &[md_idx, ins_span_md_idx, md_idx, md_idx]);
+        block.remove_instruction(&mut context, nops[3]);
+        assert_eq!(
+            context.unsafe_operations().collect::<Vec<_>>(),
+            vec![
+                (md_idx, "pointer is in bounds"),
+                (md_idx, "pointer is in bounds")
+            ]
+        );
+    }
+
+    #[test]
+    fn revert_code() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let code_md_idx =
+            MetadataIndex::new_revert_code(&mut context, 42, Some("not owner".to_owned()));
+        let with_reason = combine(&mut context, &Some(span_md_idx), &Some(code_md_idx)).unwrap();
+        let without_reason = MetadataIndex::new_revert_code(&mut context, 7, None);
+
+        // Reverts sharing a code are each listed, unless they've been optimized away.
+        let (block, nops) = new_test_nops(
+            &mut context,
+            &[with_reason, without_reason, with_reason, without_reason],
+        );
+        block.remove_instruction(&mut context, nops[3]);
+        assert_eq!(
+            context.revert_sites().collect::<Vec<_>>(),
+            vec![
+                (with_reason, 42, Some("not owner")),
+                (without_reason, 7, None),
+                (with_reason, 42, Some("not owner")),
+            ]
+        );
+    }
+
+    #[test]
+    fn drop_glue() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let scope_end_md_idx = new_test_span(&mut context, "a.sw", 27, 28);
+        let md_idx = MetadataIndex::new_drop_glue(&mut context, "File", scope_end_md_idx).unwrap();
+
+        // Drops sharing their metadata are each listed, unless they've been removed.
+        let (block, nops) =
+            new_test_nops(&mut context, &[md_idx, scope_end_md_idx, md_idx, md_idx]);
+        block.remove_instruction(&mut context, nops[3]);
+        assert_eq!(
+            context.drop_sites().collect::<Vec<_>>(),
+            vec![(md_idx, "File"), (md_idx, "File")]
+        );
+    }
+
+    #[test]
+    fn bounds_check() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let index_span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let check_md_idx =
+            MetadataIndex::new_bounds_check(&mut context, index_span_md_idx).unwrap();
+
+        // A check nested more than one list deep isn't found, as `to_bounds_check` can't see it.
+        let inner_md_idx = MetadataIndex::new_list(&mut context, vec![check_md_idx]);
+        let nested_md_idx =
+            MetadataIndex::new_list(&mut context, vec![index_span_md_idx, inner_md_idx]);
+        assert!(nested_md_idx.to_bounds_check(&context).is_err());
+
+        let (block, nops) = new_test_nops(
+            &mut context,
+            &[check_md_idx, index_span_md_idx, nested_md_idx, check_md_idx],
+        );
+        block.remove_instruction(&mut context, nops[3]);
+        let checks = context
+            .bounds_checks()
+            .map(|(value, span)| (value, span.unwrap().as_str().to_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(checks, vec![(nops[0], "x = 1".to_owned())]);
+    }
+
+    #[test]
+    fn bounds_check_unresolved_span() {
+        let source_engine = SourceEngine::default();
+        let context = crate::parse(
+            r#"script {
+    entry fn main() -> () {
+        entry():
+        v0 = nop, !2
+        v1 = const unit ()
+        ret () v1
+    }
+}
+
+!0 = "missing.sw"
+!1 = span !0 0 4
+!2 = bounds_check !1
+"#,
+            &source_engine,
+        )
+        .unwrap();
+
+        // The check is still found, even though its source can't be read.
+        let checks = context.bounds_checks().collect::<Vec<_>>();
+        assert_eq!(checks.len(), 1);
+        let (value, span) = &checks[0];
+        assert!(matches!(
+            value.get_instruction(&context),
+            Some(Instruction::Nop)
+        ));
+        assert!(span.is_err());
+    }
+}
@@ -0,0 +1,711 @@
+//! Source spans, encoded the same way as `sway-core` does: a `span` struct with an index to the
+//! source file metadatum and the start and end byte offsets, e.g., `!2 = span !1 10 20`.
+//!
+//! The source file is usually a [`Metadatum::SourceId`] but may also be a path string, as that is
+//! how parsed IR represents it.  Spans with no path, e.g., from an unsaved editor buffer or a test
+//! compiling source from a string, refer instead to an `anonymous_source` struct with the index of
+//! the source text, which is kept by the context.
+
+use std::{
+    collections::{hash_map::Entry, BTreeMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use rustc_hash::FxHashMap;
+use sway_types::{SourceId, Span};
+
+use crate::{
+    context::Context,
+    error::IrError,
+    metadata::{MetadataIndex, Metadatum},
+};
+
+impl MetadataIndex {
+    /// Create a span metadatum, and the source file metadatum it refers to if this is the first
+    /// span from that file.  For spans with no source file the source text is kept instead.
+    ///
+    /// Spans are shared, so creating the same span again returns the same index.
+    pub fn new_span(context: &mut Context, span: &Span) -> Self {
+        let file_md = match span.source_id() {
+            Some(source_id) => {
+                context
+                    .metadata_sources
+                    .entry(*source_id)
+                    .or_insert_with(|| span.src().clone());
+                Metadatum::SourceId(*source_id)
+            }
+            None => Metadatum::new_anonymous_source(context, span.src()),
+        };
+        let file_md_idx = MetadataIndex::new_canonical(context, file_md);
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "span".to_owned(),
+                vec![
+                    Metadatum::Index(file_md_idx),
+                    Metadatum::Integer(span.start() as u64),
+                    Metadatum::Integer(span.end() as u64),
+                ],
+            ),
+        )
+    }
+
+    /// Resolve the span at this index or, if it's a list, the first span within it.
+    pub fn to_span(&self, context: &Context) -> Result<Span, IrError> {
+        let (file_md_idx, start, end) = self.get_span_offsets(context)?;
+        let (source_id, src) = file_md_idx.get_file_source(context)?;
+        new_span_from_offsets(src, start, end, source_id)
+    }
+
+    /// Return the whole line of source containing the start of the span, along with the start and
+    /// end columns of the span relative to the start of that line.  For spans covering multiple
+    /// lines only the first line is returned, with the end column clamped to its end.
+    pub fn enclosing_line(&self, context: &Context) -> Result<(Arc<str>, usize, usize), IrError> {
+        let span = self.to_span(context)?;
+        let src = span.src();
+        let line_start = src[..span.start()].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = src[line_start..]
+            .find('\n')
+            .map_or(src.len(), |idx| line_start + idx);
+        let line = src[line_start..line_end].trim_end_matches('\r');
+        Ok((
+            Arc::from(line),
+            span.start() - line_start,
+            span.end().min(line_start + line.len()) - line_start,
+        ))
+    }
+
+    /// Create a struct of some text and the span it relates to, which must be a span metadatum.
+    pub(crate) fn new_text_with_span<S: Into<String>>(
+        context: &mut Context,
+        tag: &str,
+        text: S,
+        span_md_idx: MetadataIndex,
+    ) -> Result<Self, IrError> {
+        span_md_idx.verify_is_span(context)?;
+        Ok(MetadataIndex::new_struct(
+            context,
+            tag,
+            vec![
+                Metadatum::String(text.into()),
+                Metadatum::Index(span_md_idx),
+            ],
+        ))
+    }
+
+    /// Return the text and resolved span of a struct made by
+    /// [`MetadataIndex::new_text_with_span`].  `what` names the metadatum in the error if there
+    /// isn't one.
+    pub(crate) fn get_text_with_span<'a>(
+        &self,
+        context: &'a Context,
+        tag: &str,
+        what: &str,
+    ) -> Result<(&'a str, Span), IrError> {
+        let (text, span_md_idx) = self
+            .find_struct(context, tag, 2)
+            .and_then(|fields| Some((fields[0].unwrap_string()?, fields[1].unwrap_index()?)))
+            .ok_or_else(|| IrError::InvalidMetadatum(format!("Expected {what} metadatum.")))?;
+        Ok((text, span_md_idx.to_span(context)?))
+    }
+
+    /// Check that this index refers to a span, as required of metadata which link to one.  Unlike
+    /// the span accessors this doesn't look inside a list, so a list containing a span is
+    /// rejected.
+    pub(crate) fn verify_is_span(&self, context: &Context) -> Result<(), IrError> {
+        self.get_content(context)
+            .unwrap_struct("span", 3)
+            .filter(|fields| {
+                fields[0].unwrap_index().is_some()
+                    && fields[1].unwrap_integer().is_some()
+                    && fields[2].unwrap_integer().is_some()
+            })
+            .map(|_| ())
+            .ok_or_else(|| IrError::InvalidMetadatum("Expected a span metadatum.".to_owned()))
+    }
+
+    /// Return the length in bytes of the span at this index, or the first span within it if it's
+    /// a list.  The offsets aren't checked against the source, so this works even if the source
+    /// has since changed.
+    pub fn span_len(&self, context: &Context) -> Result<usize, IrError> {
+        let (_, start, end) = self.get_span_offsets(context)?;
+        end.checked_sub(start)
+            .ok_or_else(|| IrError::InvalidMetadatum("Span ends before it starts.".to_owned()))
+    }
+
+    /// Return the path of the source file and the offsets of the span at this index, or the
+    /// first span within it if it's a list.  The path is `None` for anonymous sources.  Unlike
+    /// [`MetadataIndex::to_span`] the source itself isn't needed.
+    pub(crate) fn get_span_location(
+        &self,
+        context: &Context,
+    ) -> Result<(Option<PathBuf>, usize, usize), IrError> {
+        let (file_md_idx, start, end) = self.get_span_offsets(context)?;
+        Ok((file_md_idx.get_file_path(context), start, end))
+    }
+
+    fn get_span_offsets(
+        &self,
+        context: &Context,
+    ) -> Result<(MetadataIndex, usize, usize), IrError> {
+        self.find_struct(context, "span", 3)
+            .and_then(|fields| {
+                let file_md_idx = fields[0].unwrap_index()?;
+                let start = fields[1].unwrap_integer()?;
+                let end = fields[2].unwrap_integer()?;
+                Some((file_md_idx, start as usize, end as usize))
+            })
+            .ok_or_else(|| IrError::InvalidMetadatum("Expected a span metadatum.".to_owned()))
+    }
+
+    fn get_file_path(&self, context: &Context) -> Option<PathBuf> {
+        match self.get_content(context) {
+            Metadatum::SourceId(source_id) => Some(context.source_engine().get_path(source_id)),
+            Metadatum::String(path) => Some(PathBuf::from(path)),
+            _otherwise => None,
+        }
+    }
+
+    fn get_file_source(&self, context: &Context) -> Result<(Option<SourceId>, Arc<str>), IrError> {
+        let md = self.get_content(context);
+        let source_id = match md {
+            Metadatum::SourceId(source_id) => *source_id,
+            Metadatum::String(path) => context.source_engine().get_source_id(&PathBuf::from(path)),
+            _otherwise => {
+                return md
+                    .unwrap_anonymous_source(context)
+                    .map(|src| (None, src.clone()))
+                    .ok_or_else(|| {
+                        IrError::InvalidMetadatum("Expected a source file metadatum.".to_owned())
+                    })
+            }
+        };
+        match context.metadata_sources.get(&source_id) {
+            Some(src) => Ok((Some(source_id), src.clone())),
+            None => {
+                let path = context.source_engine().get_path(&source_id);
+                std::fs::read_to_string(&path)
+                    .map(|src| (Some(source_id), Arc::from(src)))
+                    .map_err(|err| {
+                        IrError::InvalidMetadatum(format!(
+                            "Unable to read source file {}: {err}",
+                            path.display()
+                        ))
+                    })
+            }
+        }
+    }
+
+    /// Record the logical location of a region of generated code, e.g., from a `#line` style
+    /// directive, for reporting against the template it was generated from rather than the
+    /// physical source.
+    pub fn new_line_directive<S: Into<String>>(
+        context: &mut Context,
+        logical_line: usize,
+        logical_file: S,
+    ) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "line_directive",
+            vec![
+                Metadatum::Integer(logical_line as u64),
+                Metadatum::String(logical_file.into()),
+            ],
+        )
+    }
+
+    /// Return the logical file and line of a region with a line directive.
+    pub fn logical_location<'a>(&self, context: &'a Context) -> Option<(&'a str, usize)> {
+        self.find_struct(context, "line_directive", 2)
+            .and_then(|fields| {
+                Some((
+                    fields[1].unwrap_string()?,
+                    fields[0].unwrap_integer()? as usize,
+                ))
+            })
+    }
+}
+
+impl<'eng> Context<'eng> {
+    /// Resolve a batch of span metadata, in the same order as `md_idcs`.
+    ///
+    /// This is equivalent to calling [`MetadataIndex::to_span`] for each index, except that the
+    /// source of each file is looked up once for the whole batch rather than once per span.  This
+    /// matters most for files whose source isn't held by the context, which would otherwise be
+    /// read from disk for every span.
+    pub fn resolve_spans(&self, md_idcs: &[MetadataIndex]) -> Vec<Result<Span, IrError>> {
+        let mut file_sources = FxHashMap::<MetadataIndex, (Option<SourceId>, Arc<str>)>::default();
+        md_idcs
+            .iter()
+            .map(|md_idx| {
+                let (file_md_idx, start, end) = md_idx.get_span_offsets(self)?;
+                let (source_id, src) = match file_sources.entry(file_md_idx) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(file_md_idx.get_file_source(self)?),
+                };
+                new_span_from_offsets(src.clone(), start, end, *source_id)
+            })
+            .collect()
+    }
+
+    /// Replace the source text of an already registered file, e.g., after it has been edited.
+    ///
+    /// The file metadatum is unchanged so existing spans remain valid and will resolve against the
+    /// new source.  The file may be either a source ID or, as in parsed IR, a path string, but
+    /// must be referred to by at least one span.
+    pub fn update_file_source(&mut self, path: &Path, new_src: Arc<str>) -> Result<(), IrError> {
+        let source_engine = self.source_engine();
+        let source_id = self
+            .metadata
+            .iter()
+            .filter_map(|(_, md)| md.unwrap_struct("span", 3)?[0].unwrap_index())
+            .find(|file_md_idx| file_md_idx.get_file_path(self).as_deref() == Some(path))
+            .and_then(|file_md_idx| match file_md_idx.get_content(self) {
+                Metadatum::SourceId(source_id) => Some(*source_id),
+                Metadatum::String(file_path) => {
+                    Some(source_engine.get_source_id(&PathBuf::from(file_path)))
+                }
+                _otherwise => None,
+            })
+            .ok_or_else(|| {
+                IrError::InvalidMetadatum(format!(
+                    "Source file {} is not registered.",
+                    path.display()
+                ))
+            })?;
+        self.metadata_sources.insert(source_id, new_src);
+        Ok(())
+    }
+
+    /// Find any source files which have more than one metadatum, sorted by path.
+    ///
+    /// Source files are expected to be interned, so a non-empty result indicates a bug in
+    /// whatever created the metadata.
+    pub fn find_duplicate_files(&self) -> Vec<(Arc<PathBuf>, Vec<MetadataIndex>)> {
+        let mut files = BTreeMap::<Arc<PathBuf>, Vec<MetadataIndex>>::new();
+        for (arena_idx, md) in self.metadata.iter() {
+            if let Metadatum::SourceId(source_id) = md {
+                files
+                    .entry(Arc::new(self.source_engine().get_path(source_id)))
+                    .or_default()
+                    .push(MetadataIndex(arena_idx));
+            }
+        }
+        files
+            .into_iter()
+            .filter(|(_, md_idcs)| md_idcs.len() > 1)
+            .collect()
+    }
+
+    /// Return the path of the source file shared by all of the spans in `md_idcs`, or `None` if
+    /// they're not all from the same file, or if any of them isn't a span.
+    pub fn common_file(&self, md_idcs: &[MetadataIndex]) -> Option<Arc<PathBuf>> {
+        let mut file_md_idcs = md_idcs
+            .iter()
+            .map(|md_idx| md_idx.get_span_offsets(self).ok().map(|(file, ..)| file));
+        let first_file_md_idx = file_md_idcs.next()??;
+        file_md_idcs
+            .all(|file_md_idx| file_md_idx == Some(first_file_md_idx))
+            .then(|| first_file_md_idx.get_file_path(self).map(Arc::new))
+            .flatten()
+    }
+
+    /// Rewrite the offsets of every span, e.g., to relocate them after editing their source.  `f`
+    /// is passed the index and current start and end offsets of each span and returns the new
+    /// ones.
+    pub fn map_spans(&mut self, mut f: impl FnMut(MetadataIndex, usize, usize) -> (usize, usize)) {
+        let md_idcs = self
+            .metadata
+            .iter()
+            .filter(|(_, md)| md.unwrap_struct("span", 3).is_some())
+            .map(|(arena_idx, _)| MetadataIndex(arena_idx))
+            .collect::<Vec<_>>();
+        for md_idx in md_idcs {
+            let Ok((_, start, end)) = md_idx.get_span_offsets(self) else {
+                continue;
+            };
+            let (new_start, new_end) = f(md_idx, start, end);
+            if let Metadatum::Struct(_, fields) = &mut self.metadata[md_idx.0] {
+                fields[1] = Metadatum::Integer(new_start as u64);
+                fields[2] = Metadatum::Integer(new_end as u64);
+            }
+        }
+
+        // Any spans which were shared are no longer the same as their canonical form.
+        let metadata = &self.metadata;
+        self.canonical_metadata
+            .retain(|md, md_idx| metadata.get(md_idx.0) == Some(md));
+    }
+}
+
+impl Metadatum {
+    /// Return the anonymous source file metadatum for `src`, keeping the text if it's new.
+    /// Sources are told apart by their allocation rather than compared, as the text may be large.
+    pub(crate) fn new_anonymous_source(context: &mut Context, src: &Arc<str>) -> Metadatum {
+        let src_idx = match context
+            .anonymous_sources
+            .iter()
+            .position(|anon_src| Arc::ptr_eq(anon_src, src))
+        {
+            Some(src_idx) => src_idx,
+            None => {
+                context.anonymous_sources.push(src.clone());
+                context.anonymous_sources.len() - 1
+            }
+        };
+        Metadatum::Struct(
+            "anonymous_source".to_owned(),
+            vec![Metadatum::Integer(src_idx as u64)],
+        )
+    }
+
+    /// Return the source text of an anonymous source file metadatum.
+    pub(crate) fn unwrap_anonymous_source<'a>(&self, context: &'a Context) -> Option<&'a Arc<str>> {
+        self.unwrap_struct("anonymous_source", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+            .and_then(|src_idx| context.anonymous_sources.get(src_idx as usize))
+    }
+}
+
+fn new_span_from_offsets(
+    src: Arc<str>,
+    start: usize,
+    end: usize,
+    source_id: Option<SourceId>,
+) -> Result<Span, IrError> {
+    Span::new(src, start, end, source_id).ok_or_else(|| {
+        IrError::InvalidMetadatum(format!("Span offsets {start}..{end} are out of range."))
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use sway_types::{SourceEngine, Span};
+
+    use crate::{
+        context::Context,
+        metadata::{combine, MetadataIndex, Metadatum},
+    };
+
+    pub(crate) const TEST_SRC: &str = "fn main() {\n    let x = 1;\n}";
+
+    pub(crate) fn new_test_span(
+        context: &mut Context,
+        path: &str,
+        start: usize,
+        end: usize,
+    ) -> MetadataIndex {
+        let source_id = context.source_engine().get_source_id(&PathBuf::from(path));
+        let span = Span::new(Arc::from(TEST_SRC), start, end, Some(source_id)).unwrap();
+        MetadataIndex::new_span(context, &span)
+    }
+
+    #[test]
+    fn enclosing_line_mid_file() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        // `x = 1`
+        let md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        assert_eq!(md_idx.to_span(&context).unwrap().as_str(), "x = 1");
+        assert_eq!(
+            md_idx.enclosing_line(&context).unwrap(),
+            (Arc::from("    let x = 1;"), 8, 13)
+        );
+
+        // A span starting mid-line and running over the end of it only reports the first line.
+        let md_idx = new_test_span(&mut context, "a.sw", 24, 28);
+        assert_eq!(
+            md_idx.enclosing_line(&context).unwrap(),
+            (Arc::from("    let x = 1;"), 12, 14)
+        );
+    }
+
+    #[test]
+    fn enclosing_line_last_line() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let md_idx = new_test_span(&mut context, "a.sw", 27, 28);
+        assert_eq!(
+            md_idx.enclosing_line(&context).unwrap(),
+            (Arc::from("}"), 0, 1)
+        );
+
+        let int_md_idx = MetadataIndex::new_integer(&mut context, 27);
+        assert!(int_md_idx.enclosing_line(&context).is_err());
+    }
+
+    #[test]
+    fn resolve_spans_matches_to_span() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let md_idcs = new_mixed_md_idcs(&mut context);
+        let spans = context.resolve_spans(&md_idcs);
+        assert_eq!(spans.len(), md_idcs.len());
+        for (md_idx, span) in md_idcs.iter().zip(spans) {
+            match (md_idx.to_span(&context), span) {
+                (Ok(expected), Ok(span)) => {
+                    assert_eq!(span, expected);
+                    assert_eq!(span.source_id(), expected.source_id());
+                }
+                (Err(_), Err(_)) => (),
+                _otherwise => panic!("resolve_spans() and to_span() disagree."),
+            }
+        }
+    }
+
+    /// Create 1000 indices, mostly spans in a few files and the rest not spans at all.
+    fn new_mixed_md_idcs(context: &mut Context) -> Vec<MetadataIndex> {
+        (0..1000)
+            .map(|idx| match idx % 4 {
+                0 => new_test_span(context, "a.sw", idx % 20, 20),
+                1 => new_test_span(context, "b.sw", 12, 12 + idx % 16),
+                2 => new_test_span(context, "c.sw", 0, 28),
+                _ => MetadataIndex::new_integer(context, idx as u64),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn update_file_source() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let md_idx = new_test_span(&mut context, "a.sw", 3, 7);
+        assert_eq!(md_idx.to_span(&context).unwrap().as_str(), "main");
+
+        context
+            .update_file_source(&PathBuf::from("a.sw"), Arc::from("fn test() {}"))
+            .unwrap();
+        assert_eq!(md_idx.to_span(&context).unwrap().as_str(), "test");
+
+        assert!(context
+            .update_file_source(&PathBuf::from("b.sw"), Arc::from(""))
+            .is_err());
+    }
+
+    #[test]
+    fn update_file_source_parsed() {
+        let source_engine = SourceEngine::default();
+        let mut context = crate::parse(
+            r#"script {
+    entry fn main() -> () {
+        entry():
+        v0 = const unit (), !1
+        ret () v0
+    }
+}
+
+!0 = "parsed.sw"
+!1 = span !0 3 7
+"#,
+            &source_engine,
+        )
+        .unwrap();
+        let md_idx = context
+            .metadata
+            .iter()
+            .find(|(_, md)| md.unwrap_struct("span", 3).is_some())
+            .map(|(arena_idx, _)| MetadataIndex(arena_idx))
+            .unwrap();
+
+        // A path string which isn't a file, e.g., a revert message, isn't matched.
+        MetadataIndex::new_string(&mut context, "message.sw");
+        assert!(context
+            .update_file_source(&PathBuf::from("message.sw"), Arc::from(""))
+            .is_err());
+
+        context
+            .update_file_source(&PathBuf::from("parsed.sw"), Arc::from("fn test() {}"))
+            .unwrap();
+        assert_eq!(md_idx.to_span(&context).unwrap().as_str(), "test");
+    }
+
+    #[test]
+    fn find_duplicate_files() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        new_test_span(&mut context, "a.sw", 0, 2);
+        new_test_span(&mut context, "a.sw", 3, 7);
+        new_test_span(&mut context, "b.sw", 0, 2);
+        assert!(context.find_duplicate_files().is_empty());
+
+        let source_id = source_engine.get_source_id(&PathBuf::from("b.sw"));
+        let dup_md_idx = MetadataIndex::new_source_id(&mut context, source_id);
+        let dup_files = context.find_duplicate_files();
+        assert_eq!(dup_files.len(), 1);
+        assert_eq!(*dup_files[0].0, PathBuf::from("b.sw"));
+        assert_eq!(dup_files[0].1.len(), 2);
+        assert!(dup_files[0].1.contains(&dup_md_idx));
+    }
+
+    #[test]
+    fn common_file() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let a0 = new_test_span(&mut context, "a.sw", 0, 2);
+        let a1 = new_test_span(&mut context, "a.sw", 3, 7);
+        let b0 = new_test_span(&mut context, "b.sw", 0, 2);
+        let int = MetadataIndex::new_integer(&mut context, 0);
+
+        assert_eq!(
+            context.common_file(&[a0, a1]),
+            Some(Arc::new(PathBuf::from("a.sw")))
+        );
+        assert_eq!(context.common_file(&[a0, b0, a1]), None);
+        assert_eq!(context.common_file(&[a0, int]), None);
+        assert_eq!(context.common_file(&[]), None);
+    }
+
+    #[test]
+    fn span_len() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        assert_eq!(span_md_idx.span_len(&context).unwrap(), 5);
+
+        let storage_md_idx = MetadataIndex::new_struct(
+            &mut context,
+            "storage",
+            vec![Metadatum::String("reads".to_owned())],
+        );
+        assert!(storage_md_idx.span_len(&context).is_err());
+    }
+
+    #[test]
+    fn verify_is_span() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        assert!(span_md_idx.verify_is_span(&context).is_ok());
+
+        let marker_md_idx = MetadataIndex::new_pure(&mut context);
+        let merged_md_idx =
+            combine(&mut context, &Some(span_md_idx), &Some(marker_md_idx)).unwrap();
+        assert!(merged_md_idx.to_span(&context).is_ok());
+        assert!(merged_md_idx.verify_is_span(&context).is_err());
+        assert!(
+            MetadataIndex::new_text_with_span(&mut context, "note", "x", merged_md_idx).is_err()
+        );
+        assert!(MetadataIndex::new_bounds_check(&mut context, merged_md_idx).is_err());
+    }
+
+    #[test]
+    fn map_spans() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let main_md_idx = new_test_span(&mut context, "a.sw", 0, 4);
+        let x_md_idx = new_test_span(&mut context, "a.sw", 17, 22);
+        let mut visited = Vec::new();
+        context.map_spans(|md_idx, start, end| {
+            visited.push(md_idx);
+            (start + 3, end + 3)
+        });
+
+        assert_eq!(visited.len(), 2);
+        assert_eq!(main_md_idx.to_span(&context).unwrap().as_str(), "main");
+        assert_eq!(x_md_idx.to_span(&context).unwrap().as_str(), "x = 1");
+    }
+
+    #[test]
+    fn logical_location() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span_md_idx = new_test_span(&mut context, "a.sw", 20, 25);
+        let line_md_idx = MetadataIndex::new_line_directive(&mut context, 42, "template.sw.in");
+        let md_idx = combine(&mut context, &Some(span_md_idx), &Some(line_md_idx)).unwrap();
+        assert_eq!(
+            md_idx.logical_location(&context),
+            Some(("template.sw.in", 42))
+        );
+        assert_eq!(md_idx.to_span(&context).unwrap().as_str(), "x = 1");
+        assert_eq!(span_md_idx.logical_location(&context), None);
+    }
+
+    #[test]
+    fn anonymous_source() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let src: Arc<str> = Arc::from(TEST_SRC);
+        let main_md_idx =
+            MetadataIndex::new_span(&mut context, &Span::new(src.clone(), 3, 7, None).unwrap());
+        let x_md_idx =
+            MetadataIndex::new_span(&mut context, &Span::new(src.clone(), 20, 25, None).unwrap());
+        let other_md_idx = MetadataIndex::new_span(
+            &mut context,
+            &Span::new(Arc::from("script;"), 0, 6, None).unwrap(),
+        );
+        assert_eq!(context.anonymous_sources.len(), 2);
+
+        let main_span = main_md_idx.to_span(&context).unwrap();
+        assert_eq!((main_span.start(), main_span.end()), (3, 7));
+        assert_eq!(main_span.as_str(), "main");
+        assert_eq!(main_span.source_id(), None);
+        assert_eq!(x_md_idx.to_span(&context).unwrap().as_str(), "x = 1");
+        assert_eq!(other_md_idx.to_span(&context).unwrap().as_str(), "script");
+        assert_eq!(context.common_file(&[main_md_idx, x_md_idx]), None);
+    }
+
+    #[test]
+    fn anonymous_source_round_trip() {
+        let source_engine = SourceEngine::default();
+        let context = crate::parse(
+            r#"script {
+    entry fn main() -> (), !3 {
+        entry():
+        v0 = const unit ()
+        ret () v0, !4
+    }
+}
+
+!0 = anonymous_source 0 "fn main() {\n    let x = 1;\n}"
+!1 = span !0 3 7
+!2 = "\"quoted\"\t\u{1b}"
+!3 = (!1 !2)
+!4 = span !0 20 25
+"#,
+            &source_engine,
+        )
+        .unwrap();
+
+        let check = |context: &Context| {
+            let function = context.functions.iter().next().unwrap().1;
+            let md_idx = function.metadata.unwrap();
+            assert_eq!(md_idx.to_span(context).unwrap().as_str(), "main");
+            assert_eq!(
+                md_idx.get_content(context).unwrap_list().unwrap()[1]
+                    .get_content(context)
+                    .unwrap_string(),
+                Some("\"quoted\"\t\u{1b}")
+            );
+        };
+        check(&context);
+
+        // Normal dumps just refer to the source, which can't be resolved when parsed back.
+        let printed = crate::printer::to_string(&context);
+        assert!(!printed.contains("let x"));
+        let reparsed = crate::parse(&printed, &source_engine).unwrap();
+        let function = reparsed.functions.iter().next().unwrap().1;
+        assert!(function.metadata.unwrap().to_span(&reparsed).is_err());
+
+        let printed = crate::printer::to_string_with_embedded_sources(&context);
+        let reparsed = crate::parse(&printed, &source_engine).unwrap();
+        check(&reparsed);
+        assert_eq!(
+            crate::printer::to_string_with_embedded_sources(&reparsed),
+            printed
+        );
+    }
+}
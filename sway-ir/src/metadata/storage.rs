@@ -0,0 +1,432 @@
+//! Metadata describing how contract storage is laid out and accessed.
+
+use std::collections::BTreeMap;
+
+use rustc_hash::FxHashSet;
+use sway_types::state::StateIndex;
+
+use crate::{
+    context::Context,
+    metadata::{MetadataIndex, Metadatum},
+};
+
+/// The storage effects of a function or instruction.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum StorageOperation {
+    Reads,
+    Writes,
+    ReadsWrites,
+}
+
+impl StorageOperation {
+    /// Combine two storage effects into one which covers both.
+    pub fn join(self, other: StorageOperation) -> StorageOperation {
+        if self == other {
+            self
+        } else {
+            StorageOperation::ReadsWrites
+        }
+    }
+
+    /// The names used for storage effects in metadata.
+    pub fn simple_string(&self) -> &'static str {
+        match self {
+            StorageOperation::Reads => "reads",
+            StorageOperation::Writes => "writes",
+            StorageOperation::ReadsWrites => "readswrites",
+        }
+    }
+
+    pub fn from_simple_string(s: &str) -> Option<StorageOperation> {
+        match s {
+            "reads" => Some(StorageOperation::Reads),
+            "writes" => Some(StorageOperation::Writes),
+            "readswrites" => Some(StorageOperation::ReadsWrites),
+            _otherwise => None,
+        }
+    }
+}
+
+impl MetadataIndex {
+    /// Record the storage effects a function is declared with, as `sway-core` does for its
+    /// `#[storage(..)]` attribute, e.g., `!1 = storage "reads"`.
+    pub fn new_storage_attribute(context: &mut Context, op: StorageOperation) -> Self {
+        MetadataIndex::new_canonical(
+            context,
+            Metadatum::Struct(
+                "storage".to_owned(),
+                vec![Metadatum::String(op.simple_string().to_owned())],
+            ),
+        )
+    }
+
+    pub fn to_storage_attribute(&self, context: &Context) -> Option<StorageOperation> {
+        self.find_struct(context, "storage", 1)
+            .and_then(|fields| StorageOperation::from_simple_string(fields[0].unwrap_string()?))
+    }
+
+    /// Record the index of the storage field which a state access instruction refers to, i.e.,
+    /// its position in the `storage` declaration.
+    pub fn new_state_index(context: &mut Context, ix: StateIndex) -> Self {
+        MetadataIndex::new_struct(
+            context,
+            "state_index",
+            vec![Metadatum::Integer(ix.to_usize() as u64)],
+        )
+    }
+
+    pub fn to_state_idx(&self, context: &Context) -> Option<StateIndex> {
+        self.find_struct(context, "state_index", 1)
+            .and_then(|fields| fields[0].unwrap_integer())
+            .map(|ix| StateIndex::new(ix as usize))
+    }
+
+    /// Record how a storage slot key was derived: the path of the field which was hashed, e.g.,
+    /// `["balances", "alice"]`, and the domain separator which was hashed with it.
+    pub fn new_storage_slot_derivation<S: Into<String>>(
+        context: &mut Context,
+        field_path: impl IntoIterator<Item = S>,
+        domain: [u8; 32],
+    ) -> Self {
+        let fields = std::iter::once(Metadatum::new_b256(&domain))
+            .chain(
+                field_path
+                    .into_iter()
+                    .map(|segment| Metadatum::String(segment.into())),
+            )
+            .collect();
+        MetadataIndex::new_struct(context, "storage_slot_derivation", fields)
+    }
+
+    /// Return the field path and domain separator of the storage slot derivation, if any.
+    pub fn to_storage_slot_derivation<'a>(
+        &self,
+        context: &'a Context,
+    ) -> Option<(Vec<&'a str>, [u8; 32])> {
+        self.find_variadic_struct(context, "storage_slot_derivation")
+            .and_then(|fields| {
+                let (domain, field_path) = fields.split_first()?;
+                let field_path = field_path
+                    .iter()
+                    .map(Metadatum::unwrap_string)
+                    .collect::<Option<Vec<_>>>()?;
+                Some((field_path, domain.unwrap_b256()?))
+            })
+    }
+
+    /// Pin a storage field to an explicit slot, overriding the derived one.
+    pub fn new_pinned_storage_slot(context: &mut Context, slot: [u8; 32]) -> Self {
+        MetadataIndex::new_struct(context, "pinned_slot", vec![Metadatum::new_b256(&slot)])
+    }
+
+    pub fn to_pinned_storage_slot(&self, context: &Context) -> Option<[u8; 32]> {
+        self.find_struct(context, "pinned_slot", 1)
+            .and_then(|fields| fields[0].unwrap_b256())
+    }
+
+    /// Record the possible targets of an indirect call, by name, along with the
+    /// [`StorageOperation::join`] of all of their storage effects.
+    pub fn new_indirect_effect<S: Into<String>>(
+        context: &mut Context,
+        possible_targets: impl IntoIterator<Item = S>,
+        joined_storage: StorageOperation,
+    ) -> Self {
+        let fields = std::iter::once(Metadatum::String(joined_storage.simple_string().to_owned()))
+            .chain(
+                possible_targets
+                    .into_iter()
+                    .map(|target| Metadatum::String(target.into())),
+            )
+            .collect();
+        MetadataIndex::new_struct(context, "indirect_effect", fields)
+    }
+
+    /// Return the possible targets and joined storage effect of an indirect call.
+    pub fn to_indirect_effect<'a>(
+        &self,
+        context: &'a Context,
+    ) -> Option<(Vec<&'a str>, StorageOperation)> {
+        self.find_variadic_struct(context, "indirect_effect")
+            .and_then(|fields| {
+                let (joined_storage, targets) = fields.split_first()?;
+                let joined_storage =
+                    StorageOperation::from_simple_string(joined_storage.unwrap_string()?)?;
+                let targets = targets
+                    .iter()
+                    .map(Metadatum::unwrap_string)
+                    .collect::<Option<Vec<_>>>()?;
+                Some((targets, joined_storage))
+            })
+    }
+
+    /// Record a storage access along with whether it always happens or only on some paths, so
+    /// that effect summaries can tell "may write" from "always writes".
+    pub fn new_conditional_storage(
+        context: &mut Context,
+        op: StorageOperation,
+        always: bool,
+    ) -> Self {
+        let when = if always { "always" } else { "conditional" };
+        MetadataIndex::new_struct(
+            context,
+            "conditional_storage",
+            vec![
+                Metadatum::String(op.simple_string().to_owned()),
+                Metadatum::String(when.to_owned()),
+            ],
+        )
+    }
+
+    /// Return the storage operation and whether it always happens.
+    pub fn to_conditional_storage(&self, context: &Context) -> Option<(StorageOperation, bool)> {
+        self.find_struct(context, "conditional_storage", 2)
+            .and_then(|fields| {
+                let op = StorageOperation::from_simple_string(fields[0].unwrap_string()?)?;
+                let always = match fields[1].unwrap_string()? {
+                    "always" => true,
+                    "conditional" => false,
+                    _otherwise => return None,
+                };
+                Some((op, always))
+            })
+    }
+}
+
+impl<'eng> Context<'eng> {
+    /// Return an iterator for the attached metadata and slot of every pinned storage slot.  Each
+    /// index is returned once, however many values it's attached to.
+    pub fn pinned_slots(&self) -> impl Iterator<Item = (MetadataIndex, [u8; 32])> + '_ {
+        let mut seen = FxHashSet::default();
+        self.attached_metadata()
+            .into_iter()
+            .filter(move |md_idx| seen.insert(*md_idx))
+            .filter_map(|md_idx| {
+                md_idx
+                    .to_pinned_storage_slot(self)
+                    .map(|slot| (md_idx, slot))
+            })
+    }
+
+    /// Find every slot which has been pinned more than once, sorted by slot.
+    pub fn find_slot_collisions(&self) -> Vec<([u8; 32], Vec<MetadataIndex>)> {
+        let mut slots = BTreeMap::<[u8; 32], Vec<MetadataIndex>>::new();
+        for (md_idx, slot) in self.pinned_slots() {
+            slots.entry(slot).or_default().push(md_idx);
+        }
+        slots
+            .into_iter()
+            .filter(|(_, md_idcs)| md_idcs.len() > 1)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        constant::Constant,
+        context::Context,
+        function::Function,
+        irtype::Type,
+        metadata::{
+            combine,
+            tests::{check_round_trips, new_test_nops, RoundTrip},
+            MetadataIndex, StorageOperation,
+        },
+        module::{Kind, Module},
+        value::Value,
+    };
+    use sway_types::{state::StateIndex, SourceEngine};
+
+    const TEST_DOMAIN: [u8; 32] = {
+        let mut domain = [0; 32];
+        domain[0] = 0xab;
+        domain[31] = 0x01;
+        domain
+    };
+
+    #[test]
+    fn storage_attribute_and_state_index_round_trip() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let module = Module::new(&mut context, Kind::Contract);
+        let unit_ty = Type::get_unit(&context);
+        let ops = [
+            ("get", StorageOperation::Reads, 0),
+            ("set", StorageOperation::Writes, 1),
+            ("swap", StorageOperation::ReadsWrites, 2),
+        ];
+        for (name, op, ix) in ops {
+            let storage_md_idx = MetadataIndex::new_storage_attribute(&mut context, op);
+            let function = Function::new(
+                &mut context,
+                module,
+                name.to_owned(),
+                Vec::new(),
+                unit_ty,
+                None,
+                true,
+                false,
+                Some(storage_md_idx),
+            );
+            let block = function.get_entry_block(&context);
+            let state_md_idx = MetadataIndex::new_state_index(&mut context, StateIndex::new(ix));
+            let marker_md_idx = MetadataIndex::new_integer(&mut context, 0);
+            let md_idx = combine(&mut context, &Some(marker_md_idx), &Some(state_md_idx));
+            block
+                .ins(&mut context)
+                .nop()
+                .add_metadatum(&mut context, md_idx);
+            let unit = Constant::new_unit(&context);
+            let unit_val = Value::new_constant(&mut context, unit);
+            block.ins(&mut context).ret(unit_val, unit_ty);
+        }
+
+        let printed = crate::printer::to_string(&context);
+        assert!(printed.contains("storage \"readswrites\""));
+        assert!(printed.contains("state_index 2"));
+
+        let reparsed = crate::parse(&printed, &source_engine).unwrap();
+        for (name, op, ix) in ops {
+            let function = reparsed
+                .module_iter()
+                .flat_map(|module| module.function_iter(&reparsed))
+                .find(|function| function.get_name(&reparsed) == name)
+                .unwrap();
+            let md_idx = function.get_metadata(&reparsed).unwrap();
+            assert_eq!(md_idx.to_storage_attribute(&reparsed), Some(op));
+            let (_, nop) = function.instruction_iter(&reparsed).next().unwrap();
+            let md_idx = nop.get_metadata(&reparsed).unwrap();
+            assert_eq!(md_idx.to_state_idx(&reparsed), Some(StateIndex::new(ix)));
+            assert_eq!(md_idx.to_storage_attribute(&reparsed), None);
+        }
+    }
+
+    #[test]
+    fn unknown_storage_attribute() {
+        let source_engine = SourceEngine::default();
+        let Err(err) = crate::parse(
+            r#"contract {
+    fn get() -> (), !0 {
+        entry():
+        v0 = const unit ()
+        ret () v0
+    }
+}
+
+!0 = storage "read"
+"#,
+            &source_engine,
+        ) else {
+            panic!("Unknown storage attribute was parsed.");
+        };
+        assert!(err.to_string().contains("storage read"));
+    }
+
+    #[test]
+    fn round_trips() {
+        let round_trips: [RoundTrip; 8] = [
+            (
+                |context| MetadataIndex::new_storage_attribute(context, StorageOperation::Reads),
+                |md_idx, context| {
+                    md_idx.to_storage_attribute(context) == Some(StorageOperation::Reads)
+                },
+            ),
+            (
+                |context| MetadataIndex::new_state_index(context, StateIndex::new(2)),
+                |md_idx, context| md_idx.to_state_idx(context) == Some(StateIndex::new(2)),
+            ),
+            (
+                |context| {
+                    MetadataIndex::new_storage_slot_derivation(
+                        context,
+                        ["balances", "alice"],
+                        TEST_DOMAIN,
+                    )
+                },
+                |md_idx, context| {
+                    md_idx.to_storage_slot_derivation(context)
+                        == Some((vec!["balances", "alice"], TEST_DOMAIN))
+                },
+            ),
+            (
+                |context| MetadataIndex::new_pinned_storage_slot(context, [0x12; 32]),
+                |md_idx, context| md_idx.to_pinned_storage_slot(context) == Some([0x12; 32]),
+            ),
+            (
+                |context| MetadataIndex::new_pinned_storage_slot(context, [0x34; 32]),
+                |md_idx, context| md_idx.to_pinned_storage_slot(context) == Some([0x34; 32]),
+            ),
+            (
+                |context| {
+                    MetadataIndex::new_indirect_effect(
+                        context,
+                        ["get_balance", "set_balance"],
+                        StorageOperation::ReadsWrites,
+                    )
+                },
+                |md_idx, context| {
+                    md_idx.to_indirect_effect(context)
+                        == Some((
+                            vec!["get_balance", "set_balance"],
+                            StorageOperation::ReadsWrites,
+                        ))
+                },
+            ),
+            (
+                |context| {
+                    MetadataIndex::new_conditional_storage(context, StorageOperation::Writes, false)
+                },
+                |md_idx, context| {
+                    md_idx.to_conditional_storage(context)
+                        == Some((StorageOperation::Writes, false))
+                },
+            ),
+            (
+                |context| {
+                    MetadataIndex::new_conditional_storage(context, StorageOperation::Reads, true)
+                },
+                |md_idx, context| {
+                    md_idx.to_conditional_storage(context) == Some((StorageOperation::Reads, true))
+                },
+            ),
+        ];
+        check_round_trips(&round_trips);
+    }
+
+    #[test]
+    fn pinned_storage_slots() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let slot_a = [0x12; 32];
+        let slot_b = [0x34; 32];
+        let a0 = MetadataIndex::new_pinned_storage_slot(&mut context, slot_a);
+        let b0 = MetadataIndex::new_pinned_storage_slot(&mut context, slot_b);
+
+        // A pin attached to several values doesn't collide with itself, and a pin which isn't
+        // attached to anything doesn't collide at all.
+        let (block, _) = new_test_nops(&mut context, &[a0, b0, a0]);
+        assert_eq!(context.pinned_slots().count(), 2);
+        let a1 = MetadataIndex::new_pinned_storage_slot(&mut context, slot_a);
+        assert!(context.find_slot_collisions().is_empty());
+
+        block
+            .ins(&mut context)
+            .nop()
+            .add_metadatum(&mut context, Some(a1));
+        let collisions = context.find_slot_collisions();
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].0, slot_a);
+        assert!(collisions[0].1.contains(&a0) && collisions[0].1.contains(&a1));
+        assert!(!collisions[0].1.contains(&b0));
+    }
+
+    #[test]
+    fn storage_operation_join() {
+        assert_eq!(
+            StorageOperation::Reads.join(StorageOperation::Writes),
+            StorageOperation::ReadsWrites
+        );
+    }
+}
@@ -7,6 +7,7 @@ use std::collections::{BTreeMap, HashMap};
 use crate::{
     context::Context,
     function::{Function, FunctionIterator},
+    metadata::{combine, MetadataIndex},
     value::Value,
 };
 
@@ -21,6 +22,7 @@ pub struct ModuleContent {
     pub functions: Vec<Function>,
     pub global_constants: HashMap<Vec<String>, Value>,
     pub global_configurable: BTreeMap<Vec<String>, Value>,
+    pub metadata: Option<MetadataIndex>,
 }
 
 /// The different 'kinds' of Sway module: `Contract`, `Library`, `Predicate` or `Script`.
@@ -40,6 +42,7 @@ impl Module {
             functions: Vec::new(),
             global_constants: HashMap::new(),
             global_configurable: BTreeMap::new(),
+            metadata: None,
         };
         Module(context.modules.insert(content))
     }
@@ -49,6 +52,21 @@ impl Module {
         context.modules[self.0].kind
     }
 
+    /// Add some metadata to this module.
+    pub fn add_metadatum(self, context: &mut Context, md_idx: Option<MetadataIndex>) -> Self {
+        if md_idx.is_some() {
+            let orig_md = context.modules[self.0].metadata;
+            let new_md = combine(context, &orig_md, &md_idx);
+            context.modules[self.0].metadata = new_md;
+        }
+        self
+    }
+
+    /// Return this module's metadata.
+    pub fn get_metadata(&self, context: &Context) -> Option<MetadataIndex> {
+        context.modules[self.0].metadata
+    }
+
     /// Return an interator over each of the [`Function`]s in this module.
     pub fn function_iter(&self, context: &Context) -> FunctionIterator {
         FunctionIterator::new(context, self)
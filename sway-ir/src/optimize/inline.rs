@@ -16,7 +16,7 @@ use crate::{
     instruction::{FuelVmInstruction, Instruction},
     irtype::Type,
     local_var::LocalVar,
-    metadata::{combine, MetadataIndex},
+    metadata::{combine, InlineHint, MetadataIndex},
     value::{Value, ValueContent, ValueDatum},
     AnalysisResults, BlockArgument, Module, Pass, PassMutability, ScopedPass,
 };
@@ -43,51 +43,12 @@ pub fn create_inline_in_module_pass() -> Pass {
     }
 }
 
-/// This is a copy of sway_core::inline::Inline.
-/// TODO: Reuse: Depend on sway_core? Move it to sway_types?
-#[derive(Debug)]
-enum Inline {
-    Always,
-    Never,
-}
 /// This is a copy of sway_core::asm_generation::compiler_constants.
 /// TODO: Once we have a target specific IR generator / legalizer,
 ///       use that to mark related functions as ALWAYS_INLINE.
 ///       Then we no longer depend on this const value below.
 const NUM_ARG_REGISTERS: u8 = 6;
 
-fn metadata_to_inline(context: &Context, md_idx: Option<MetadataIndex>) -> Option<Inline> {
-    fn for_each_md_idx<T, F: FnMut(MetadataIndex) -> Option<T>>(
-        context: &Context,
-        md_idx: Option<MetadataIndex>,
-        mut f: F,
-    ) -> Option<T> {
-        // If md_idx is not None and is a list then try them all.
-        md_idx.and_then(|md_idx| {
-            if let Some(md_idcs) = md_idx.get_content(context).unwrap_list() {
-                md_idcs.iter().find_map(|md_idx| f(*md_idx))
-            } else {
-                f(md_idx)
-            }
-        })
-    }
-    for_each_md_idx(context, md_idx, |md_idx| {
-        // Create a new inline and save it in the cache.
-        md_idx
-            .get_content(context)
-            .unwrap_struct("inline", 1)
-            .and_then(|fields| fields[0].unwrap_string())
-            .and_then(|inline_str| {
-                let inline = match inline_str {
-                    "always" => Some(Inline::Always),
-                    "never" => Some(Inline::Never),
-                    _otherwise => None,
-                }?;
-                Some(inline)
-            })
-    })
-}
-
 pub fn inline_in_module(
     context: &mut Context,
     _: &AnalysisResults,
@@ -110,16 +71,18 @@ pub fn inline_in_module(
             });
 
     let inline_heuristic = |ctx: &Context, func: &Function, _call_site: &Value| {
-        let attributed_inline = metadata_to_inline(ctx, func.get_metadata(ctx));
+        let attributed_inline = func
+            .get_metadata(ctx)
+            .map_or(InlineHint::Default, |md_idx| md_idx.inline_hint(ctx));
         match attributed_inline {
-            Some(Inline::Always) => {
+            InlineHint::Always => {
                 // TODO: check if inlining of function is possible
                 // return true;
             }
-            Some(Inline::Never) => {
+            InlineHint::Never => {
                 return false;
             }
-            None => {}
+            InlineHint::Default => {}
         }
 
         let ret_type = func.get_return_type(ctx);
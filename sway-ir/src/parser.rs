@@ -575,15 +575,25 @@ mod ir_builder {
             // This rule (uniquely) does NOT discard the newline whitespace. `__` matches only
             // spaces.
             rule metadata_item() -> IrMetadatum
-                = i:dec_digits() __ {
+                = "0x" s:$(['0'..='9' | 'a'..='f' | 'A'..='F']+) __ {?
+                    // Integers are always printed in decimal but may be written in hex, e.g., for
+                    // selectors.
+                    u64::from_str_radix(s, 16)
+                        .map(IrMetadatum::Integer)
+                        .map_err(|_| "64 bit integer")
+                }
+                / i:dec_digits() __ {
                     IrMetadatum::Integer(i)
                 }
                 / "!" idx:dec_digits() __ {
                     IrMetadatum::Index(idx)
                 }
-                / ['"'] s:$(([^ '"' | '\\'] / ['\\'] ['\\' | '"' ])+) ['"'] __ {
-                    // Metadata strings are printed with '\\' escaped on parsing we unescape it.
-                    IrMetadatum::String(s.to_owned().replace("\\\\", "\\"))
+                / ['"'] s:$(([^ '"' | '\\'] / ['\\'] [_])*) ['"'] __ {?
+                    // Metadata strings are printed escaped, as by `{:?}`, so on parsing we
+                    // unescape them.
+                    unescape_string(s)
+                        .map(IrMetadatum::String)
+                        .ok_or("valid escape sequence")
                 }
                 / tag:$(id_char0() id_char()*) __ els:metadata_item()* {
                     IrMetadatum::Struct(tag.to_owned(), els)
@@ -646,7 +656,7 @@ mod ir_builder {
         instruction::{Instruction, Predicate, Register},
         irtype::Type,
         local_var::LocalVar,
-        metadata::{MetadataIndex, Metadatum},
+        metadata::{MetadataIndex, Metadatum, StorageOperation},
         module::{Kind, Module},
         value::Value,
         BinaryOpKind, BlockArgument, UnaryOpKind,
@@ -898,14 +908,14 @@ mod ir_builder {
 
     // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 
-    use std::{collections::HashMap, iter::FromIterator};
+    use std::{collections::HashMap, iter::FromIterator, sync::Arc};
 
     pub(super) fn build_context(
         ir_ast_mod: IrAstModule,
         source_engine: &SourceEngine,
     ) -> Result<Context, IrError> {
         let mut ctx = Context::new(source_engine);
-        let md_map = build_metadata_map(&mut ctx, ir_ast_mod.metadata);
+        let md_map = build_metadata_map(&mut ctx, ir_ast_mod.metadata)?;
         let module = Module::new(&mut ctx, ir_ast_mod.kind);
         let mut builder = IrBuilder {
             module,
@@ -1290,7 +1300,10 @@ mod ir_builder {
                             *val_map.get(&src_name).unwrap(),
                         )
                         .add_metadatum(context, opt_metadata),
-                    IrAstOperation::Nop => block.ins(context).nop(),
+                    IrAstOperation::Nop => block
+                        .ins(context)
+                        .nop()
+                        .add_metadatum(context, opt_metadata),
                     IrAstOperation::PtrToInt(val, ty) => {
                         let to_ty = ty.to_ir_type(context);
                         block
@@ -1444,7 +1457,7 @@ mod ir_builder {
     fn build_metadata_map(
         context: &mut Context,
         ir_metadata: Vec<(MdIdxRef, IrMetadatum)>,
-    ) -> HashMap<MdIdxRef, MetadataIndex> {
+    ) -> Result<HashMap<MdIdxRef, MetadataIndex>, IrError> {
         fn convert_md(md: IrMetadatum, md_map: &mut HashMap<MdIdxRef, MetadataIndex>) -> Metadatum {
             match md {
                 IrMetadatum::Integer(i) => Metadatum::Integer(i),
@@ -1477,11 +1490,70 @@ mod ir_builder {
         let mut md_map = HashMap::new();
 
         for (ir_idx, ir_md) in ir_metadata {
-            let md = convert_md(ir_md, &mut md_map);
+            let md = match convert_md(ir_md, &mut md_map) {
+                // An anonymous source with its text embedded is registered with the context,
+                // which gives it a new index.
+                Metadatum::Struct(tag, fields)
+                    if tag == "anonymous_source" && fields.len() == 2 =>
+                {
+                    match &fields[1] {
+                        Metadatum::String(src) => {
+                            Metadatum::new_anonymous_source(context, &Arc::from(src.as_str()))
+                        }
+                        _otherwise => Metadatum::Struct(tag, fields),
+                    }
+                }
+                // Storage attributes are checked here rather than when they're used, which is
+                // typically much later in codegen.
+                Metadatum::Struct(tag, fields)
+                    if tag == "storage"
+                        && !matches!(fields.as_slice(), [Metadatum::String(op)]
+                            if StorageOperation::from_simple_string(op).is_some()) =>
+                {
+                    return Err(IrError::ParseFailure(
+                        "storage reads, writes or readswrites".to_owned(),
+                        format!(
+                            "storage {}",
+                            fields
+                                .first()
+                                .and_then(Metadatum::unwrap_string)
+                                .unwrap_or_default()
+                        ),
+                    ));
+                }
+                md => md,
+            };
             let md_idx = MetadataIndex(context.metadata.insert(md));
             md_map.insert(ir_idx, md_idx);
         }
-        md_map
+        Ok(md_map)
+    }
+
+    /// Undo the escaping done by `{:?}` when printing a string.
+    fn unescape_string(s: &str) -> Option<String> {
+        let mut unescaped = String::with_capacity(s.len());
+        let mut chars = s.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                unescaped.push(ch);
+                continue;
+            }
+            unescaped.push(match chars.next()? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                '0' => '\0',
+                'u' => {
+                    let hex = chars.as_str().strip_prefix('{')?.split_once('}')?.0;
+                    let ch = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+                    chars = chars.as_str()[hex.len() + 2..].chars();
+                    ch
+                }
+                ch @ ('\\' | '"' | '\'') => ch,
+                _otherwise => return None,
+            });
+        }
+        Some(unescaped)
     }
 
     fn string_to_hex<const N: usize>(s: &str) -> [u8; N] {
@@ -6,8 +6,6 @@
 
 use std::collections::{BTreeMap, HashMap};
 
-use sway_types::SourceEngine;
-
 use crate::{
     asm::*,
     block::Block,
@@ -90,7 +88,23 @@ impl Doc {
 ///
 /// The ouput from this function must always be suitable for [`crate::parser::parse`].
 pub fn to_string(context: &Context) -> String {
-    let mut md_namer = MetadataNamer::default();
+    context_to_string(context, MetadataNamer::default())
+}
+
+/// Like [`to_string`] but the text of any anonymous sources, i.e., sources without a path which
+/// the parser can't otherwise find, is embedded in the metadata so that their spans survive being
+/// parsed back.  This is off by default as whole source files would otherwise clutter dumps.
+pub fn to_string_with_embedded_sources(context: &Context) -> String {
+    context_to_string(
+        context,
+        MetadataNamer {
+            embed_sources: true,
+            ..MetadataNamer::default()
+        },
+    )
+}
+
+fn context_to_string(context: &Context, mut md_namer: MetadataNamer) -> String {
     context
         .modules
         .iter()
@@ -1152,6 +1166,7 @@ impl Namer {
 struct MetadataNamer {
     md_map: BTreeMap<MetadataIndex, u64>,
     next_md_idx: u64,
+    embed_sources: bool,
 }
 
 impl MetadataNamer {
@@ -1221,11 +1236,7 @@ impl MetadataNamer {
     }
 
     fn to_doc(&self, context: &Context) -> Doc {
-        fn md_to_string(
-            md_namer: &MetadataNamer,
-            md: &Metadatum,
-            source_engine: &SourceEngine,
-        ) -> String {
+        fn md_to_string(md_namer: &MetadataNamer, md: &Metadatum, context: &Context) -> String {
             match md {
                 Metadatum::Integer(i) => i.to_string(),
                 Metadatum::Index(idx) => format!(
@@ -1236,17 +1247,20 @@ impl MetadataNamer {
                 ),
                 Metadatum::String(s) => format!("{s:?}"),
                 Metadatum::SourceId(id) => {
-                    let path = source_engine.get_path(id);
+                    let path = context.source_engine.get_path(id);
                     format!("{path:?}")
                 }
                 Metadatum::Struct(tag, els) => {
-                    format!(
-                        "{tag} {}",
-                        els.iter()
-                            .map(|el_md| md_to_string(md_namer, el_md, source_engine))
-                            .collect::<Vec<_>>()
-                            .join(" ")
-                    )
+                    let mut els = els
+                        .iter()
+                        .map(|el_md| md_to_string(md_namer, el_md, context))
+                        .collect::<Vec<_>>();
+                    if md_namer.embed_sources {
+                        if let Some(src) = md.unwrap_anonymous_source(context) {
+                            els.push(format!("{src:?}"));
+                        }
+                    }
+                    format!("{tag} {}", els.join(" "))
                 }
                 Metadatum::List(idcs) => {
                     format!(
@@ -1270,7 +1284,7 @@ impl MetadataNamer {
             .map(|(ref_idx, md_idx)| {
                 Doc::text_line(format!(
                     "!{ref_idx} = {}",
-                    md_to_string(self, &context.metadata[md_idx.0], context.source_engine)
+                    md_to_string(self, &context.metadata[md_idx.0], context)
                 ))
             })
             .collect::<Vec<_>>();
@@ -0,0 +1,217 @@
+//! A consolidated map from IR instructions to their source locations, e.g., for the debugger to
+//! set breakpoints with.
+//!
+//! Every instruction gets an entry, so long as its function or an earlier instruction in its block
+//! has a span.  Instructions without a span of their own inherit the span of the nearest
+//! preceding instruction in the same block, or failing that the span of their function, and are
+//! marked as such.
+
+use std::path::{Path, PathBuf};
+
+use rustc_hash::FxHashMap;
+
+use crate::{context::Context, function::Function, module::Module};
+
+/// A map from instructions to source locations, with each source file path stored once.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    /// The distinct source file paths, referred to by [`SourceMapEntry::path_idx`].
+    pub paths: Vec<PathBuf>,
+    /// An entry for each instruction, in function, block and instruction order.
+    pub entries: Vec<SourceMapEntry>,
+
+    path_idcs: FxHashMap<PathBuf, usize>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SourceMapEntry {
+    pub function: Function,
+    /// The index of the block within the function.
+    pub block_idx: usize,
+    /// The index of the instruction within the block.
+    pub instr_idx: usize,
+    /// The index of the source file path in [`SourceMap::paths`], or `None` for sources without
+    /// a path, e.g., compiled from an in-memory buffer.
+    pub path_idx: Option<usize>,
+    pub start: usize,
+    pub end: usize,
+    /// Whether the instruction has no span of its own and this one is inherited.
+    pub inherited: bool,
+}
+
+impl SourceMap {
+    /// Return the path of the source file of an entry.
+    pub fn get_path(&self, entry: &SourceMapEntry) -> Option<&Path> {
+        entry
+            .path_idx
+            .map(|path_idx| self.paths[path_idx].as_path())
+    }
+
+    fn add_function(&mut self, context: &Context, function: Function) {
+        let fn_location = function
+            .get_metadata(context)
+            .and_then(|md_idx| md_idx.get_span_location(context).ok());
+        for (block_idx, block) in function.block_iter(context).enumerate() {
+            let mut prev_location = fn_location.clone();
+            for (instr_idx, ins_value) in block.instruction_iter(context).enumerate() {
+                let location = ins_value
+                    .get_metadata(context)
+                    .and_then(|md_idx| md_idx.get_span_location(context).ok());
+                let inherited = location.is_none();
+                let Some((path, start, end)) = location.or_else(|| prev_location.clone()) else {
+                    continue;
+                };
+                let path_idx = path.as_ref().map(|path| self.get_path_idx(path));
+                self.entries.push(SourceMapEntry {
+                    function,
+                    block_idx,
+                    instr_idx,
+                    path_idx,
+                    start,
+                    end,
+                    inherited,
+                });
+                prev_location = Some((path, start, end));
+            }
+        }
+    }
+
+    fn get_path_idx(&mut self, path: &Path) -> usize {
+        if let Some(path_idx) = self.path_idcs.get(path) {
+            return *path_idx;
+        }
+        let path_idx = self.paths.len();
+        self.paths.push(path.to_owned());
+        self.path_idcs.insert(path.to_owned(), path_idx);
+        path_idx
+    }
+}
+
+impl Function {
+    /// Build a [`SourceMap`] for the instructions of this function.
+    pub fn build_source_map(&self, context: &Context) -> SourceMap {
+        let mut source_map = SourceMap::default();
+        source_map.add_function(context, *self);
+        source_map
+    }
+}
+
+impl Module {
+    /// Build a [`SourceMap`] for the instructions of every function in this module.
+    pub fn build_source_map(&self, context: &Context) -> SourceMap {
+        let mut source_map = SourceMap::default();
+        for function in self.function_iter(context) {
+            source_map.add_function(context, function);
+        }
+        source_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use sway_types::{SourceEngine, Span};
+
+    use crate::{
+        constant::Constant, context::Context, instruction::Instruction, irtype::Type,
+        metadata::MetadataIndex, value::Value,
+    };
+
+    #[test]
+    fn build_source_map() {
+        let source_engine = SourceEngine::default();
+        let context = crate::parse(
+            r#"script {
+    entry fn main() -> (), !1 {
+        entry():
+        v0 = nop, !2
+        v1 = nop
+        br next()
+
+        next():
+        v2 = nop
+        v3 = nop, !4
+        v4 = const unit ()
+        ret () v4, !5
+    }
+
+    fn other() -> () {
+        entry():
+        v0 = nop
+        v1 = nop, !6
+        v2 = const unit ()
+        ret () v2
+    }
+}
+
+!0 = "a.sw"
+!1 = span !0 0 40
+!2 = span !0 10 20
+!3 = "b.sw"
+!4 = span !3 5 9
+!5 = span !0 22 30
+!6 = span !3 1 3
+"#,
+            &source_engine,
+        )
+        .unwrap();
+
+        let module = context.module_iter().next().unwrap();
+        let source_map = module.build_source_map(&context);
+        assert_eq!(source_map.paths.len(), 2);
+
+        let entries = source_map
+            .entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.function.get_name(&context),
+                    entry.block_idx,
+                    entry.instr_idx,
+                    source_map.get_path(entry).unwrap(),
+                    entry.start,
+                    entry.end,
+                    entry.inherited,
+                )
+            })
+            .collect::<Vec<_>>();
+        let (a, b) = (Path::new("a.sw"), Path::new("b.sw"));
+        assert_eq!(
+            entries,
+            vec![
+                ("main", 0, 0, a, 10, 20, false),
+                ("main", 0, 1, a, 10, 20, true),
+                ("main", 0, 2, a, 10, 20, true),
+                ("main", 1, 0, a, 0, 40, true),
+                ("main", 1, 1, b, 5, 9, false),
+                ("main", 1, 2, a, 22, 30, false),
+                // The first instruction of `other` has nothing to inherit.
+                ("other", 0, 1, b, 1, 3, false),
+                ("other", 0, 2, b, 1, 3, true),
+            ]
+        );
+
+        let main = module.function_iter(&context).next().unwrap();
+        assert_eq!(
+            main.build_source_map(&context).entries,
+            source_map.entries[..6]
+        );
+    }
+
+    #[test]
+    fn value_get_span() {
+        let source_engine = SourceEngine::default();
+        let mut context = Context::new(&source_engine);
+
+        let span = Span::new(Arc::from("let x = 1;"), 4, 9, None).unwrap();
+        let span_md_idx = MetadataIndex::new_span(&mut context, &span);
+        let unit = Constant::new_unit(&context);
+        let unit_val = Value::new_constant(&mut context, unit);
+        let unit_ty = Type::get_unit(&context);
+        let ret_val = Value::new_instruction(&mut context, Instruction::Ret(unit_val, unit_ty))
+            .add_metadatum(&mut context, Some(span_md_idx));
+        assert_eq!(ret_val.get_span(&context).unwrap().as_str(), "x = 1");
+        assert_eq!(unit_val.get_span(&context), None);
+    }
+}
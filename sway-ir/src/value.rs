@@ -7,6 +7,7 @@
 //! therefore also easy to replace, a common practice for optimization passes.
 
 use rustc_hash::FxHashMap;
+use sway_types::Span;
 
 use crate::{
     block::BlockArgument,
@@ -97,6 +98,12 @@ impl Value {
         context.values[self.0].metadata
     }
 
+    /// Return the source span of this value, if it has one.
+    pub fn get_span(&self, context: &Context) -> Option<Span> {
+        self.get_metadata(context)
+            .and_then(|md_idx| md_idx.to_span(context).ok())
+    }
+
     /// Return whether this is a constant value.
     pub fn is_configurable(&self, context: &Context) -> bool {
         matches!(context.values[self.0].value, ValueDatum::Configurable(_))
@@ -3,6 +3,8 @@
 //! During creation, deserialization and optimization the IR should be verified to be in a
 //! consistent valid state, using the functions in this module.
 
+use rustc_hash::FxHashSet;
+
 use crate::{
     block::BlockContent,
     context::Context,
@@ -11,7 +13,7 @@ use crate::{
     instruction::{FuelVmInstruction, Instruction, Predicate},
     irtype::Type,
     local_var::LocalVar,
-    metadata::{MetadataIndex, Metadatum},
+    metadata::{verify_struct_tag, MetadataIndex, Metadatum, StorageOperation},
     module::ModuleContent,
     value::{Value, ValueDatum},
     AnalysisResult, AnalysisResultT, AnalysisResults, BinaryOpKind, BlockArgument,
@@ -74,6 +76,8 @@ impl<'eng> Context<'eng> {
             }
         }
 
+        self.verify_storage_purity(function)?;
+
         let function = &self.functions[function.0];
         for block in &function.blocks {
             self.verify_block(cur_module, function, &self.blocks[block.0])?;
@@ -82,6 +86,89 @@ impl<'eng> Context<'eng> {
         Ok(())
     }
 
+    /// Check that the storage accesses of a function, by its own state instructions and ASM
+    /// blocks or by the functions it calls, are permitted by its storage attribute.  As in
+    /// `sway-core`, a function which may write to storage may also read from it.
+    fn verify_storage_purity(&self, function: &Function) -> Result<(), IrError> {
+        let get_storage_attribute = |function: &Function| {
+            function
+                .get_metadata(self)
+                .and_then(|md_idx| md_idx.to_storage_attribute(self))
+        };
+        let permits = |attr: Option<StorageOperation>, op: StorageOperation| match attr {
+            None => false,
+            Some(StorageOperation::Reads) => op == StorageOperation::Reads,
+            Some(StorageOperation::Writes | StorageOperation::ReadsWrites) => true,
+        };
+        let attr = get_storage_attribute(function);
+        let attr_name = || attr.map_or("none", |attr| attr.simple_string()).to_owned();
+
+        for (_, ins_value) in function.instruction_iter(self) {
+            let span = || {
+                ins_value
+                    .get_metadata(self)
+                    .and_then(|md_idx| md_idx.to_span(self).ok())
+            };
+            match ins_value.get_instruction(self) {
+                Some(Instruction::Call(callee, _)) => {
+                    let Some(callee_attr) = get_storage_attribute(callee) else {
+                        continue;
+                    };
+                    if !permits(attr, callee_attr) {
+                        return Err(IrError::VerifyStorageMismatchInCall {
+                            function: function.get_name(self).to_owned(),
+                            callee: callee.get_name(self).to_owned(),
+                            expected: attr_name(),
+                            found: callee_attr.simple_string().to_owned(),
+                            span: span(),
+                        });
+                    }
+                }
+                Some(ins) => {
+                    let Some(op) = self.get_storage_access(ins) else {
+                        continue;
+                    };
+                    if !permits(attr, op) {
+                        return Err(IrError::VerifyStorageMismatch {
+                            function: function.get_name(self).to_owned(),
+                            expected: attr_name(),
+                            found: op.simple_string().to_owned(),
+                            span: span(),
+                        });
+                    }
+                }
+                None => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Return how an instruction accesses storage, if at all.
+    fn get_storage_access(&self, ins: &Instruction) -> Option<StorageOperation> {
+        match ins {
+            Instruction::FuelVm(FuelVmInstruction::StateLoadQuadWord { .. })
+            | Instruction::FuelVm(FuelVmInstruction::StateLoadWord(_)) => {
+                Some(StorageOperation::Reads)
+            }
+            Instruction::FuelVm(FuelVmInstruction::StateClear { .. })
+            | Instruction::FuelVm(FuelVmInstruction::StateStoreQuadWord { .. })
+            | Instruction::FuelVm(FuelVmInstruction::StateStoreWord { .. }) => {
+                Some(StorageOperation::Writes)
+            }
+            Instruction::AsmBlock(asm_block, _) => asm_block
+                .get_content(self)
+                .body
+                .iter()
+                .filter_map(|asm_ins| match asm_ins.name.as_str() {
+                    "srw" | "srwq" => Some(StorageOperation::Reads),
+                    "scwq" | "sww" | "swwq" => Some(StorageOperation::Writes),
+                    _otherwise => None,
+                })
+                .reduce(StorageOperation::join),
+            _otherwise => None,
+        }
+    }
+
     fn verify_block(
         &self,
         cur_module: &ModuleContent,
@@ -126,36 +213,46 @@ impl<'eng> Context<'eng> {
     }
 
     fn verify_metadata(&self, md_idx: Option<MetadataIndex>) -> Result<(), IrError> {
-        // For now we check only that struct tags are valid identiers.
-        if let Some(md_idx) = md_idx {
-            match &self.metadata[md_idx.0] {
-                Metadatum::List(md_idcs) => {
-                    for md_idx in md_idcs {
-                        self.verify_metadata(Some(*md_idx))?;
-                    }
-                }
-                Metadatum::Struct(tag, ..) => {
-                    // We could import Regex to match it, but it's a simple identifier style pattern:
-                    // alpha start char, alphanumeric for the rest, or underscore anywhere.
-                    if tag.is_empty() {
-                        return Err(IrError::InvalidMetadatum(
-                            "Struct has empty tag.".to_owned(),
-                        ));
-                    }
-                    let mut chs = tag.chars();
-                    let ch0 = chs.next().unwrap();
-                    if !(ch0.is_ascii_alphabetic() || ch0 == '_')
-                        || chs.any(|ch| !(ch.is_ascii_alphanumeric() || ch == '_'))
-                    {
-                        return Err(IrError::InvalidMetadatum(format!(
-                            "Invalid struct tag: '{tag}'."
-                        )));
-                    }
-                }
-                _otherwise => (),
+        // For now we check only that struct tags are valid identifiers, that indices refer to
+        // existing metadata and that there are no cycles, which would send lookups into an
+        // endless loop.
+        fn verify_md_idx(
+            context: &Context,
+            md_idx: MetadataIndex,
+            on_path: &mut FxHashSet<MetadataIndex>,
+            verified: &mut FxHashSet<MetadataIndex>,
+        ) -> Result<(), IrError> {
+            if verified.contains(&md_idx) {
+                return Ok(());
             }
+            if !on_path.insert(md_idx) {
+                return Err(IrError::InvalidMetadatum(
+                    "Metadata contain a cycle.".to_owned(),
+                ));
+            }
+            let md = context.metadata.get(md_idx.0).ok_or_else(|| {
+                IrError::InvalidMetadatum("Index refers to a missing metadatum.".to_owned())
+            })?;
+            if let Metadatum::Struct(tag, _) = md {
+                verify_struct_tag(tag)?;
+            }
+            for ref_md_idx in md.referenced_indices() {
+                verify_md_idx(context, ref_md_idx, on_path, verified)?;
+            }
+            on_path.remove(&md_idx);
+            verified.insert(md_idx);
+            Ok(())
+        }
+
+        match md_idx {
+            Some(md_idx) => verify_md_idx(
+                self,
+                md_idx,
+                &mut FxHashSet::default(),
+                &mut FxHashSet::default(),
+            ),
+            None => Ok(()),
         }
-        Ok(())
     }
 }
 
@@ -966,3 +1063,142 @@ impl<'a, 'eng> InstructionVerifier<'a, 'eng> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use sway_types::SourceEngine;
+
+    use crate::error::IrError;
+
+    /// Parse, and so verify, a function `f` with storage attribute `attr` which runs `body`.
+    /// A local `key` is in `v0` for `body` to access storage with.
+    fn verify_storage_access(attr: Option<&str>, body: &str) -> Option<IrError> {
+        let (md_ref, md) = match attr {
+            Some(attr) => (", !0", format!("!0 = storage \"{attr}\"")),
+            None => ("", String::new()),
+        };
+        let source_engine = SourceEngine::default();
+        crate::parse(
+            &format!(
+                "contract {{
+    fn f() -> (){md_ref} {{
+        local b256 key
+
+        entry():
+        v0 = get_local ptr b256, key
+        {body}
+        v9 = const unit ()
+        ret () v9
+    }}
+}}
+
+{md}
+"
+            ),
+            &source_engine,
+        )
+        .err()
+    }
+
+    const LOAD: &str = "v1 = state_load_word key v0";
+    const STORE: &str = "v1 = const u64 0
+        state_store_word v1, key v0";
+    const ASM_LOAD: &str = "v1 = asm(r1: v0, r2) -> u64 r2 {
+            srw    r2 r1
+        }";
+
+    #[test]
+    fn storage_purity() {
+        let is_mismatch = |err: Option<IrError>, op: &str| matches!(err, Some(IrError::VerifyStorageMismatch { found, .. }) if found == op);
+
+        assert!(verify_storage_access(None, "").is_none());
+        assert!(is_mismatch(verify_storage_access(None, LOAD), "reads"));
+        assert!(is_mismatch(verify_storage_access(None, STORE), "writes"));
+        assert!(is_mismatch(verify_storage_access(None, ASM_LOAD), "reads"));
+
+        assert!(verify_storage_access(Some("reads"), LOAD).is_none());
+        assert!(verify_storage_access(Some("reads"), ASM_LOAD).is_none());
+        assert!(is_mismatch(
+            verify_storage_access(Some("reads"), STORE),
+            "writes"
+        ));
+
+        assert!(verify_storage_access(Some("writes"), STORE).is_none());
+        assert!(verify_storage_access(Some("writes"), LOAD).is_none());
+
+        assert!(verify_storage_access(Some("readswrites"), LOAD).is_none());
+        assert!(verify_storage_access(Some("readswrites"), STORE).is_none());
+    }
+
+    #[test]
+    fn storage_purity_error_span() {
+        let source_engine = SourceEngine::default();
+        let err = crate::parse(
+            r#"contract {
+    fn f() -> (), !0 {
+        local b256 key
+
+        entry():
+        v0 = get_local ptr b256, key
+        v1 = const u64 0
+        state_store_word v1, key v0, !2
+        v2 = const unit ()
+        ret () v2
+    }
+}
+
+!0 = storage "reads"
+!1 = anonymous_source 0 "fn f() {\n    storage.x.write(0);\n}"
+!2 = span !1 13 31
+"#,
+            &source_engine,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Verification failed: Function 'f' with storage attribute 'reads' writes storage at \
+            2:5 `storage.x.write(0)`."
+        );
+    }
+
+    #[test]
+    fn storage_purity_of_calls() {
+        let verify_call = |caller_attr: &str, callee_attr: &str| {
+            let source_engine = SourceEngine::default();
+            crate::parse(
+                &format!(
+                    "contract {{
+    fn g() -> (), !0 {{
+        entry():
+        v0 = const unit ()
+        ret () v0
+    }}
+
+    fn f() -> (), !1 {{
+        entry():
+        v0 = call g()
+        ret () v0
+    }}
+}}
+
+!0 = storage \"{callee_attr}\"
+!1 = storage \"{caller_attr}\"
+"
+                ),
+                &source_engine,
+            )
+            .err()
+        };
+
+        assert!(matches!(
+            verify_call("reads", "readswrites"),
+            Some(IrError::VerifyStorageMismatchInCall { function, callee, .. })
+                if function == "f" && callee == "g"
+        ));
+        assert!(verify_call("reads", "writes").is_some());
+        assert!(verify_call("reads", "reads").is_none());
+        assert!(verify_call("writes", "reads").is_none());
+        assert!(verify_call("readswrites", "writes").is_none());
+    }
+}